@@ -0,0 +1,198 @@
+use anyhow::{Context, Result, anyhow};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{
+    env, fmt, fs,
+    path::{Path, PathBuf},
+    process,
+};
+
+use momitroll_util::process::shell_command;
+
+#[derive(Deserialize, Serialize, JsonSchema)]
+#[serde(tag = "source", rename_all = "kebab-case")]
+pub enum Credentials {
+    // names of environment variables
+    Env {
+        username: String,
+        password: Option<String>,
+    },
+    // files of secret mounts, username can be given in place
+    File {
+        username: Option<String>,
+        #[serde(rename = "username-file", alias = "username_file")]
+        username_file: Option<PathBuf>,
+        #[serde(rename = "password-file", alias = "password_file")]
+        password_file: PathBuf,
+    },
+    // stdout of shell command is password, e.g. `vault kv get -field=password secret/mongo`
+    Command {
+        username: String,
+        #[serde(rename = "password-command", alias = "password_command")]
+        password_command: String,
+    },
+    // credentials are part of `db.uri`
+    Uri,
+    None,
+}
+
+impl Credentials {
+    // username and optional password which are set over connection string
+    // password command runs in directory of config
+    pub fn resolve(&self, dir: &Path) -> Result<Option<(String, Option<String>)>> {
+        self.read(dir)
+            .with_context(|| format!("failed to read credentials from {self}"))
+    }
+
+    fn read(&self, dir: &Path) -> Result<Option<(String, Option<String>)>> {
+        match self {
+            Credentials::Env { username, password } => {
+                let var = |name: &str| {
+                    env::var(name)
+                        .map_err(|e| anyhow!("environment variable {name} is not usable: {e}"))
+                };
+
+                Ok(Some((
+                    var(username)?,
+                    password.as_deref().map(var).transpose()?,
+                )))
+            }
+            Credentials::File {
+                username,
+                username_file,
+                password_file,
+            } => {
+                let username = match (username, username_file) {
+                    (Some(username), _) => username.clone(),
+                    (None, Some(path)) => read_secret_file(path)?,
+                    (None, None) => {
+                        return Err(anyhow!("`username` or `username-file` is required"));
+                    }
+                };
+
+                Ok(Some((username, Some(read_secret_file(password_file)?))))
+            }
+            Credentials::Command {
+                username,
+                password_command,
+            } => {
+                let output = shell_command(password_command)
+                    .current_dir(dir)
+                    .stderr(process::Stdio::inherit())
+                    .output()
+                    .context("failed to run password command")?;
+
+                if !output.status.success() {
+                    return Err(anyhow!("password command exited with {}", output.status));
+                }
+
+                let password = trim_secret(String::from_utf8(output.stdout)?);
+
+                if password.is_empty() {
+                    return Err(anyhow!("password command printed nothing"));
+                }
+
+                Ok(Some((username.clone(), Some(password))))
+            }
+            Credentials::Uri | Credentials::None => Ok(None),
+        }
+    }
+}
+
+impl fmt::Display for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Credentials::Env { .. } => write!(f, "source `env`"),
+            Credentials::File { .. } => write!(f, "source `file`"),
+            Credentials::Command { .. } => write!(f, "source `command`"),
+            Credentials::Uri => write!(f, "source `uri`"),
+            Credentials::None => write!(f, "source `none`"),
+        }
+    }
+}
+
+fn read_secret_file(path: &PathBuf) -> Result<String> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("failed to read secret file {}", path.display()))?;
+
+    Ok(trim_secret(content))
+}
+
+// secret files and command output usually end with newline
+fn trim_secret(secret: String) -> String {
+    secret.trim_end_matches(['\n', '\r']).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn env_credentials_are_read_from_variables() {
+        // SAFETY: names are unique to this test, no other thread reads them
+        unsafe {
+            env::set_var("MOMITROLL_TEST_CREDS_USER", "app");
+            env::set_var("MOMITROLL_TEST_CREDS_PASSWORD", "secret");
+        }
+
+        let credentials = Credentials::Env {
+            username: "MOMITROLL_TEST_CREDS_USER".to_string(),
+            password: Some("MOMITROLL_TEST_CREDS_PASSWORD".to_string()),
+        };
+        let missing = Credentials::Env {
+            username: "MOMITROLL_TEST_CREDS_MISSING".to_string(),
+            password: None,
+        };
+
+        assert_eq!(
+            credentials.resolve(Path::new(".")).unwrap(),
+            Some(("app".to_string(), Some("secret".to_string())))
+        );
+        assert!(missing.resolve(Path::new(".")).is_err());
+    }
+
+    #[test]
+    fn file_credentials_take_username_in_place() {
+        let file = env::temp_dir().join(format!("momitroll-{}-password", process::id()));
+        fs::write(&file, "secret\n").unwrap();
+
+        let credentials = Credentials::File {
+            username: Some("app".to_string()),
+            username_file: None,
+            password_file: file.clone(),
+        };
+        let res = credentials.resolve(Path::new("."));
+        fs::remove_file(file).unwrap();
+
+        assert_eq!(
+            res.unwrap(),
+            Some(("app".to_string(), Some("secret".to_string())))
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn command_credentials_run_in_config_dir() {
+        let dir = env::temp_dir().join(format!("momitroll-{}-command", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("password.txt"), "secret\n").unwrap();
+
+        let credentials = Credentials::Command {
+            username: "app".to_string(),
+            password_command: "cat password.txt".to_string(),
+        };
+        let failing = Credentials::Command {
+            username: "app".to_string(),
+            password_command: "exit 3".to_string(),
+        };
+        let res = credentials.resolve(&dir);
+        let failed = failing.resolve(&dir);
+        fs::remove_dir_all(dir).unwrap();
+
+        assert_eq!(
+            res.unwrap(),
+            Some(("app".to_string(), Some("secret".to_string())))
+        );
+        assert!(failed.is_err());
+    }
+}
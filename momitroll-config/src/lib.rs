@@ -1,61 +1,538 @@
-use anyhow::{Result, anyhow};
-use serde::Deserialize;
-use std::{env, path::PathBuf};
+mod credentials;
+mod overrides;
+mod scaffold;
+mod validate;
+
+use anyhow::{Context, Result, anyhow};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    env, fmt,
+    path::{Path, PathBuf},
+};
+
+use overrides::{apply, leaves};
+
+pub use credentials::Credentials;
+pub use overrides::{ENV_PREFIX, Override, Overrides, ValueSource, misspelled_env_vars};
+pub use scaffold::{ConfigFormat, Scaffold};
+pub use validate::{Problem, Severity, schema, validate};
 
 pub const CONFIG_FILE_NAME: &str = "momitroll-config";
 
-#[derive(Deserialize)]
+// table of environment profiles, e.g. `[env.prod.db]`
+pub(crate) const ENVS_KEY: &str = "env";
+
+#[derive(Deserialize, Serialize, JsonSchema)]
 pub struct Config {
+    // selected environment profile and file of config, set by loader
+    #[serde(skip)]
+    pub env: Option<String>,
+    #[serde(skip)]
+    pub path: PathBuf,
+    #[serde(skip)]
+    pub sources: BTreeMap<String, ValueSource>,
     pub migration: MigrationConfig,
     pub db: DbConfig,
+    // credentials are not used when both are missing, e.g. for local instance or X.509
+    pub credentials: Option<Credentials>,
     #[serde(rename = "creds-env-vars")]
-    pub creds_env_vars: CredEnvVars,
+    pub creds_env_vars: Option<CredEnvVars>,
+    #[serde(default)]
+    pub hooks: HooksConfig,
+    // declared vars and `MOMITROLL_*` are the only environment variables seen by migrations
+    #[serde(default)]
+    pub vars: BTreeMap<String, VarValue>,
+    #[serde(default)]
+    pub seed: SeedConfig,
+    #[serde(default)]
+    pub validators: ValidatorsConfig,
+    #[serde(default)]
+    pub indexes: IndexesConfig,
+    // production databases refuse destructive developer commands like `seed`
+    #[serde(default)]
+    pub production: bool,
+    // iana name of timezone of displayed times, e.g. `Europe/Kyiv`, utc when missing
+    pub timezone: Option<String>,
 }
 
 impl Config {
-    pub fn load() -> Result<Self> {
-        match find_config_file()? {
-            ConfigFile::TOML(path) => {
-                let config: Config = toml::from_str(&std::fs::read_to_string(path)?)?;
-                Ok(config)
+    pub fn load(path: Option<&Path>, env: Option<&str>, overrides: &Overrides) -> Result<Self> {
+        let file = Self::locate(path)?;
+        let value: Value = match &file {
+            ConfigFile::TOML(path) => toml::from_str(&std::fs::read_to_string(path)?)?,
+            ConfigFile::JSON(path) => serde_json::from_str(&std::fs::read_to_string(path)?)?,
+        };
+        let path = PathBuf::from(file);
+
+        let mut config = Self::from_value(value, env, overrides)
+            .with_context(|| format!("invalid config file: {}", path.display()))?;
+        config.path = path.canonicalize()?;
+        config.resolve_paths();
+
+        Ok(config)
+    }
+
+    // relative paths of config are resolved against its directory, not current one
+    fn resolve_paths(&mut self) {
+        let dir = self.dir().to_path_buf();
+        let resolve = |path: &mut String| {
+            *path = dir.join(&*path).to_string_lossy().to_string();
+        };
+
+        resolve(&mut self.migration.dir);
+        resolve(&mut self.seed.dir);
+        resolve(&mut self.validators.dir);
+        resolve(&mut self.indexes.file);
+
+        for hook in self.hooks.all_mut() {
+            if let Some(migration) = &mut hook.migration {
+                resolve(migration);
             }
-            ConfigFile::JSON(path) => {
-                let config: Config = serde_json::from_str(&std::fs::read_to_string(path)?)?;
-                Ok(config)
+        }
+
+        if let Some(tls) = &mut self.db.tls {
+            for file in [&mut tls.ca_file, &mut tls.cert_key_file]
+                .into_iter()
+                .flatten()
+            {
+                *file = dir.join(&*file);
+            }
+        }
+
+        if let Some(Credentials::File {
+            username_file,
+            password_file,
+            ..
+        }) = &mut self.credentials
+        {
+            for file in [username_file.as_mut(), Some(password_file)]
+                .into_iter()
+                .flatten()
+            {
+                *file = dir.join(&*file);
+            }
+        }
+    }
+
+    // legacy `creds-env-vars` is the same as `env` source of `credentials`
+    pub fn resolve_credentials(&self) -> Result<Option<(String, Option<String>)>> {
+        match (&self.credentials, &self.creds_env_vars) {
+            (Some(_), Some(_)) => Err(anyhow!(
+                "`credentials` and `creds-env-vars` can't be used together"
+            )),
+            (Some(Credentials::Uri), _) if self.db.uri.is_none() => {
+                Err(anyhow!("credentials source `uri` requires `db.uri`"))
+            }
+            (Some(credentials), _) => credentials.resolve(self.dir()),
+            (None, Some(vars)) => vars
+                .get_creds()
+                .map(Some)
+                .context("failed to read credentials from `creds-env-vars`"),
+            (None, None) => Ok(None),
+        }
+    }
+
+    // explicit path wins over search from current directory
+    pub fn locate(path: Option<&Path>) -> Result<ConfigFile> {
+        match path {
+            Some(path) => ConfigFile::from_path(path.to_path_buf()),
+            None => find_config_file(&env::current_dir()?),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        self.path.parent().unwrap_or(Path::new("."))
+    }
+
+    pub fn timezone(&self) -> Result<chrono_tz::Tz> {
+        match &self.timezone {
+            Some(name) => name
+                .parse()
+                .map_err(|_| anyhow!("unknown timezone: {name}")),
+            None => Ok(chrono_tz::UTC),
+        }
+    }
+
+    // profile of environment is merged over base config, which holds common defaults,
+    // overrides go last
+    pub(crate) fn from_value(
+        mut value: Value,
+        env: Option<&str>,
+        overrides: &Overrides,
+    ) -> Result<Self> {
+        let envs = value
+            .as_object_mut()
+            .and_then(|root| root.remove(ENVS_KEY))
+            .unwrap_or_default();
+        let mut sources = BTreeMap::new();
+        let mut record = |value: &Value, source: ValueSource| {
+            let mut paths = vec![];
+            leaves(value, "", &mut paths);
+            for (path, _) in paths {
+                sources.insert(path, source.clone());
+            }
+        };
+
+        record(&value, ValueSource::File);
+
+        if let Some(name) = env {
+            let profile = envs.get(name).ok_or_else(|| {
+                let available = envs
+                    .as_object()
+                    .map(|envs| envs.keys().cloned().collect::<Vec<_>>().join(", "))
+                    .unwrap_or_default();
+                anyhow!("environment `{name}` is not defined, available: [{available}]")
+            })?;
+
+            record(profile, ValueSource::Profile(name.to_string()));
+            merge(&mut value, profile.clone());
+        }
+
+        for Override {
+            key,
+            value: raw,
+            source,
+        } in &overrides.0
+        {
+            apply(&mut value, key, raw)?;
+            sources.insert(key.join("."), source.clone());
+        }
+
+        let mut config: Config = serde_json::from_value(value)?;
+        config.env = env.map(str::to_string);
+        config.sources = sources;
+
+        Ok(config)
+    }
+
+    // effective values with their sources, nested source of override wins over its parent
+    pub fn entries(&self) -> Result<Vec<(String, Value, ValueSource)>> {
+        let mut paths = vec![];
+        leaves(&serde_json::to_value(self)?, "", &mut paths);
+
+        Ok(paths
+            .into_iter()
+            .map(|(path, value)| {
+                let parts = path.split('.').collect::<Vec<_>>();
+                let source = (1..=parts.len())
+                    .rev()
+                    .find_map(|n| self.sources.get(&parts[..n].join(".")))
+                    .cloned()
+                    .unwrap_or(ValueSource::Default);
+                (path, value, source)
+            })
+            .collect())
+    }
+}
+
+// sources of credentials are alternatives with different fields, so a profile which sets one
+// of them replaces both, they are never merged key by key
+const CREDENTIAL_KEYS: [&str; 2] = ["credentials", "creds-env-vars"];
+
+fn merge(base: &mut Value, profile: Value) {
+    if let (Value::Object(base), Value::Object(profile)) = (&mut *base, &profile)
+        && CREDENTIAL_KEYS.iter().any(|key| profile.contains_key(*key))
+    {
+        for key in CREDENTIAL_KEYS {
+            base.remove(key);
+        }
+    }
+
+    merge_tables(base, profile);
+}
+
+// tables are merged key by key, other values including arrays are replaced
+fn merge_tables(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(base) => merge_tables(base, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
             }
         }
+        (base, overlay) => *base = overlay,
     }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize, JsonSchema)]
 pub struct MigrationConfig {
     pub dir: String,
     #[serde(rename = "changelog-coll-name")]
     pub changelog_coll_name: String,
+    #[serde(rename = "data-batch-size", default = "default_data_batch_size")]
+    pub data_batch_size: usize,
+    // regenerate schema snapshot file after every `up` and `down`
+    #[serde(default)]
+    pub snapshot: bool,
+    #[serde(rename = "snapshot-file", default = "default_snapshot_file")]
+    pub snapshot_file: String,
+    // events of run history expire after this many days, kept forever when missing
+    #[serde(rename = "history-ttl-days")]
+    pub history_ttl_days: Option<u32>,
+}
+
+fn default_data_batch_size() -> usize {
+    1000
+}
+
+fn default_snapshot_file() -> String {
+    "schema.snapshot.json".to_string()
 }
 
 impl MigrationConfig {
     pub fn coll_name(&self) -> String {
         format!("_{}", self.changelog_coll_name)
     }
+
+    pub fn lock_coll_name(&self) -> String {
+        format!("_{}_lock", self.changelog_coll_name)
+    }
+
+    pub fn history_coll_name(&self) -> String {
+        format!("_{}_history", self.changelog_coll_name)
+    }
+
+    pub fn snapshot_path(&self) -> PathBuf {
+        PathBuf::from(&self.dir).join(&self.snapshot_file)
+    }
+}
+
+#[derive(Deserialize, Serialize, JsonSchema)]
+pub struct SeedConfig {
+    #[serde(default = "default_seed_dir")]
+    pub dir: String,
+    // seed sets loaded by default, empty means every set in the directory
+    #[serde(default)]
+    pub sets: Vec<String>,
+}
+
+impl Default for SeedConfig {
+    fn default() -> Self {
+        Self {
+            dir: default_seed_dir(),
+            sets: vec![],
+        }
+    }
+}
+
+fn default_seed_dir() -> String {
+    "seeds".to_string()
+}
+
+#[derive(Deserialize, Serialize, JsonSchema)]
+pub struct ValidatorsConfig {
+    #[serde(default = "default_validators_dir")]
+    pub dir: String,
+    #[serde(rename = "validation-level", default = "default_validation_level")]
+    pub validation_level: String,
+    #[serde(rename = "validation-action", default = "default_validation_action")]
+    pub validation_action: String,
+}
+
+impl Default for ValidatorsConfig {
+    fn default() -> Self {
+        Self {
+            dir: default_validators_dir(),
+            validation_level: default_validation_level(),
+            validation_action: default_validation_action(),
+        }
+    }
+}
+
+fn default_validators_dir() -> String {
+    "validators".to_string()
+}
+
+fn default_validation_level() -> String {
+    "strict".to_string()
+}
+
+fn default_validation_action() -> String {
+    "error".to_string()
+}
+
+#[derive(Deserialize, Serialize, JsonSchema)]
+pub struct IndexesConfig {
+    #[serde(default = "default_indexes_file")]
+    pub file: String,
+}
+
+impl Default for IndexesConfig {
+    fn default() -> Self {
+        Self {
+            file: default_indexes_file(),
+        }
+    }
 }
 
-#[derive(Deserialize)]
+fn default_indexes_file() -> String {
+    "indexes.json".to_string()
+}
+
+#[derive(Deserialize, Serialize, JsonSchema)]
 pub struct DbConfig {
+    // full connection string, e.g. `mongodb+srv://cluster0.example.net/?retryWrites=true`,
+    // `host`, `port`, `hosts` and `srv` are ignored when it's set
+    pub uri: Option<String>,
+    #[serde(default = "default_host")]
     pub host: String,
+    #[serde(default = "default_port")]
     pub port: u16,
+    // `host:port` members of replica set, replace `host` and `port`
+    #[serde(default)]
+    pub hosts: Vec<String>,
+    // resolve `host` as DNS seed list like `mongodb+srv` scheme
+    #[serde(default)]
+    pub srv: bool,
     pub name: String,
+    #[serde(rename = "replica-set", alias = "replica_set")]
+    pub replica_set: Option<String>,
+    // `admin` is used for structured fields if it's not set
+    #[serde(rename = "auth-source", alias = "auth_source")]
+    pub auth_source: Option<String>,
+    // SCRAM-SHA-1, SCRAM-SHA-256, MONGODB-X509, PLAIN, ...
+    #[serde(rename = "auth-mechanism", alias = "auth_mechanism")]
+    pub auth_mechanism: Option<String>,
+    pub tls: Option<TlsConfig>,
 }
 
-#[derive(Deserialize)]
+fn default_host() -> String {
+    "localhost".to_string()
+}
+
+fn default_port() -> u16 {
+    27017
+}
+
+impl DbConfig {
+    // connection string without credentials, they are set separately
+    pub fn connection_string(&self) -> String {
+        if let Some(uri) = &self.uri {
+            return uri.clone();
+        }
+
+        if self.srv {
+            return format!("mongodb+srv://{}/", self.host);
+        }
+
+        match self.hosts.is_empty() {
+            true => format!("mongodb://{}:{}/", self.host, self.port),
+            false => format!("mongodb://{}/", self.hosts.join(",")),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, JsonSchema)]
+pub struct TlsConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    #[serde(rename = "ca-file", alias = "ca_file")]
+    pub ca_file: Option<PathBuf>,
+    // PEM file with client certificate and private key, required for X.509
+    #[serde(rename = "cert-key-file", alias = "cert_key_file")]
+    pub cert_key_file: Option<PathBuf>,
+    #[serde(
+        rename = "allow-invalid-certificates",
+        alias = "allow_invalid_certificates",
+        default
+    )]
+    pub allow_invalid_certificates: bool,
+}
+
+#[derive(Deserialize, Serialize, JsonSchema)]
 pub struct CredEnvVars {
     pub username: String,
-    pub password: String,
+    // not needed for mechanisms without password like MONGODB-X509
+    pub password: Option<String>,
 }
 
 impl CredEnvVars {
-    pub fn get_creds(&self) -> Result<(String, String)> {
-        Ok((env::var(&self.username)?, env::var(&self.password)?))
+    pub fn get_creds(&self) -> Result<(String, Option<String>)> {
+        Credentials::from(self)
+            .resolve(Path::new("."))?
+            .ok_or_else(|| anyhow!("environment variables gave no credentials"))
+    }
+}
+
+impl From<&CredEnvVars> for Credentials {
+    fn from(vars: &CredEnvVars) -> Self {
+        Credentials::Env {
+            username: vars.username.clone(),
+            password: vars.password.clone(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, JsonSchema, Default)]
+pub struct HooksConfig {
+    #[serde(rename = "before-up", alias = "before_up", default)]
+    pub before_up: Vec<Hook>,
+    #[serde(rename = "after-up", alias = "after_up", default)]
+    pub after_up: Vec<Hook>,
+    #[serde(rename = "before-each", alias = "before_each", default)]
+    pub before_each: Vec<Hook>,
+    #[serde(rename = "after-each", alias = "after_each", default)]
+    pub after_each: Vec<Hook>,
+    #[serde(rename = "on-failure", alias = "on_failure", default)]
+    pub on_failure: Vec<Hook>,
+}
+
+#[derive(Deserialize, Serialize, JsonSchema)]
+pub struct Hook {
+    // shell command, migration metadata is passed in MOMITROLL_* environment variables
+    pub command: Option<String>,
+    // path of migration file which commands are applied
+    pub migration: Option<String>,
+    // run hook only around these migrations, empty means all of them
+    #[serde(default)]
+    pub only: Vec<String>,
+    #[serde(
+        rename = "abort-on-failure",
+        alias = "abort_on_failure",
+        default = "default_true"
+    )]
+    pub abort_on_failure: bool,
+}
+
+impl HooksConfig {
+    fn all_mut(&mut self) -> impl Iterator<Item = &mut Hook> {
+        self.before_up
+            .iter_mut()
+            .chain(&mut self.after_up)
+            .chain(&mut self.before_each)
+            .chain(&mut self.after_each)
+            .chain(&mut self.on_failure)
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Deserialize, Serialize, JsonSchema)]
+#[serde(untagged)]
+pub enum VarValue {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Bool(bool),
+}
+
+impl fmt::Display for VarValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VarValue::String(v) => write!(f, "{v}"),
+            VarValue::Integer(v) => write!(f, "{v}"),
+            VarValue::Float(v) => write!(f, "{v}"),
+            VarValue::Bool(v) => write!(f, "{v}"),
+        }
     }
 }
 
@@ -78,25 +555,169 @@ impl ConfigFile {
     pub fn is_valid_extension(ext: &str) -> bool {
         ext == "toml" || ext == "json"
     }
+
+    pub fn from_path(path: PathBuf) -> Result<Self> {
+        if !path.is_file() {
+            return Err(anyhow!("config file doesn't exist: {}", path.display()));
+        }
+
+        match path.extension().and_then(|s| s.to_str()) {
+            Some("toml") => Ok(ConfigFile::TOML(path)),
+            Some("json") => Ok(ConfigFile::JSON(path)),
+            _ => Err(anyhow!(
+                "config file must have .toml or .json extension: {}",
+                path.display()
+            )),
+        }
+    }
 }
 
-pub fn find_config_file() -> Result<ConfigFile> {
-    use std::env;
-    use walkdir::WalkDir;
+// looks for `momitroll-config.{toml,json}` in `start` directory and then in its parents
+pub fn find_config_file(start: &Path) -> Result<ConfigFile> {
+    let mut tried = vec![];
 
-    let current_dir = env::current_dir()?;
+    for dir in start.ancestors() {
+        for ext in ["toml", "json"] {
+            let path = dir.join(format!("{CONFIG_FILE_NAME}.{ext}"));
 
-    for entry in WalkDir::new(&current_dir) {
-        let entry = entry?;
-        let path = entry.path();
-        let extension = path.extension().and_then(|s| s.to_str());
+            if path.is_file() {
+                return ConfigFile::from_path(path);
+            }
 
-        match extension {
-            Some("toml") => return Ok(ConfigFile::TOML(path.to_path_buf())),
-            Some("json") => return Ok(ConfigFile::JSON(path.to_path_buf())),
-            _ => continue,
+            tried.push(format!("  {}", path.display()));
         }
     }
 
-    Err(anyhow!("didn't found config file"))
+    Err(anyhow!(
+        "didn't find config file, tried:\n{}",
+        tried.join("\n")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn config(profile: Value) -> Config {
+        let value = json!({
+            "migration": { "dir": "migrations", "changelog-coll-name": "changelog" },
+            "db": { "name": "app", "port": 27017, "tls": { "ca-file": "ca.pem" } },
+            "credentials": { "source": "env", "username": "MONGO_USERNAME", "password": "MONGO_PASSWORD" },
+            "env": { "prod": profile },
+        });
+
+        Config::from_value(value, Some("prod"), &Overrides::default()).unwrap()
+    }
+
+    #[test]
+    fn profile_overrides_scalars() {
+        let config = config(json!({ "db": { "name": "prod_app", "port": 27018 } }));
+
+        assert_eq!(config.db.name, "prod_app");
+        assert_eq!(config.db.port, 27018);
+        assert_eq!(config.migration.dir, "migrations");
+        assert_eq!(config.env.as_deref(), Some("prod"));
+    }
+
+    #[test]
+    fn profile_merges_nested_tables() {
+        let config = config(json!({ "db": { "tls": { "allow-invalid-certificates": true } } }));
+        let tls = config.db.tls.unwrap();
+
+        assert!(tls.allow_invalid_certificates);
+        assert_eq!(tls.ca_file, Some(PathBuf::from("ca.pem")));
+        assert_eq!(config.db.name, "app");
+    }
+
+    #[test]
+    fn profile_replaces_credentials() {
+        let file = config(json!({
+            "credentials": { "source": "file", "password-file": "/run/secrets/password" },
+        }));
+
+        assert!(matches!(
+            file.credentials,
+            Some(Credentials::File {
+                username: None,
+                username_file: None,
+                ..
+            })
+        ));
+
+        let legacy = config(json!({
+            "creds-env-vars": { "username": "PROD_USERNAME" },
+        }));
+
+        assert!(legacy.credentials.is_none());
+        assert_eq!(legacy.creds_env_vars.unwrap().username, "PROD_USERNAME");
+    }
+
+    #[test]
+    fn undefined_profile_is_error() {
+        let value = json!({ "env": { "prod": {} } });
+        let err = Config::from_value(value, Some("stage"), &Overrides::default())
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            err.to_string(),
+            "environment `stage` is not defined, available: [prod]"
+        );
+    }
+
+    // fresh directory tree in temp dir, `a/b` inside it
+    fn tree(name: &str) -> PathBuf {
+        let root = env::temp_dir().join(format!("momitroll-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("a/b")).unwrap();
+        root
+    }
+
+    #[test]
+    fn config_file_is_found_in_parent_dir() {
+        let root = tree("parent");
+        std::fs::write(root.join("a/momitroll-config.json"), "{}").unwrap();
+
+        let file = find_config_file(&root.join("a/b")).unwrap();
+
+        assert!(matches!(file, ConfigFile::JSON(_)));
+        assert_eq!(PathBuf::from(file), root.join("a/momitroll-config.json"));
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn config_file_of_current_dir_wins() {
+        let root = tree("current");
+        std::fs::write(root.join("a/momitroll-config.toml"), "").unwrap();
+        std::fs::write(root.join("a/b/momitroll-config.toml"), "").unwrap();
+
+        let file = find_config_file(&root.join("a/b")).unwrap();
+
+        assert_eq!(PathBuf::from(file), root.join("a/b/momitroll-config.toml"));
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn missing_config_file_lists_tried_paths() {
+        let root = tree("missing");
+
+        let err = find_config_file(&root.join("a/b"))
+            .err()
+            .unwrap()
+            .to_string();
+        let tried = err.lines().skip(1).map(str::trim).collect::<Vec<_>>();
+
+        assert!(err.starts_with("didn't find config file, tried:"));
+        assert_eq!(
+            tried[..4],
+            [
+                root.join("a/b/momitroll-config.toml").display().to_string(),
+                root.join("a/b/momitroll-config.json").display().to_string(),
+                root.join("a/momitroll-config.toml").display().to_string(),
+                root.join("a/momitroll-config.json").display().to_string(),
+            ]
+        );
+        std::fs::remove_dir_all(root).unwrap();
+    }
 }
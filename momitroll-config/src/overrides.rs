@@ -0,0 +1,313 @@
+use anyhow::{Result, anyhow};
+use serde_json::{Map, Value};
+use std::{env, fmt};
+
+pub const ENV_PREFIX: &str = "MOMITROLL_";
+
+// environment variables of cli itself and of hook processes, they aren't config keys
+const RESERVED_ENV_VARS: [&str; 6] = [
+    "MOMITROLL_ENV",
+    "MOMITROLL_CONFIG",
+    "MOMITROLL_OUTPUT",
+    "MOMITROLL_LOG_FORMAT",
+    "MOMITROLL_LOG_FILE",
+    "MOMITROLL_LOG_ROTATION",
+];
+
+// set by momitroll for hook processes and migration templates
+const RUNTIME_ENV_VARS: [&str; 8] = [
+    "MOMITROLL_HOOK",
+    "MOMITROLL_DIRECTION",
+    "MOMITROLL_DB_NAME",
+    "MOMITROLL_MIGRATION_DIR",
+    "MOMITROLL_MIGRATION",
+    "MOMITROLL_MIGRATION_DESCRIPTION",
+    "MOMITROLL_ERROR",
+    "MOMITROLL_NOW",
+];
+
+// tables of config as they are spelled in variable names
+const SECTIONS: [&str; 8] = [
+    "DB",
+    "MIGRATION",
+    "SEED",
+    "VALIDATORS",
+    "INDEXES",
+    "HOOKS",
+    "CREDENTIALS",
+    "CREDS_ENV_VARS",
+];
+
+// scalars at top level of config, they have no `__`
+const TOP_LEVEL_KEYS: [&str; 2] = ["production", "timezone"];
+
+// precedence from lowest: default, file, profile, environment variable, flag
+#[derive(Clone)]
+pub enum ValueSource {
+    Default,
+    File,
+    Profile(String),
+    EnvVar(String),
+    Flag,
+}
+
+impl fmt::Display for ValueSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValueSource::Default => write!(f, "default"),
+            ValueSource::File => write!(f, "file"),
+            ValueSource::Profile(name) => write!(f, "env profile {name}"),
+            ValueSource::EnvVar(name) => write!(f, "environment variable {name}"),
+            ValueSource::Flag => write!(f, "--override flag"),
+        }
+    }
+}
+
+pub struct Override {
+    pub key: Vec<String>,
+    pub value: String,
+    pub source: ValueSource,
+}
+
+#[derive(Default)]
+pub struct Overrides(pub Vec<Override>);
+
+impl Overrides {
+    // `MOMITROLL_DB__HOST` is `db.host` and `MOMITROLL_MIGRATION__CHANGELOG_COLL_NAME`
+    // is `migration.changelog-coll-name`, `--override db.port=27018` uses config keys as is
+    pub fn new(flags: &[String]) -> Result<Self> {
+        let mut overrides = vec![];
+
+        for (name, value) in env::vars() {
+            if let Some(key) = env_var_key(&name) {
+                overrides.push(Override {
+                    key,
+                    value,
+                    source: ValueSource::EnvVar(name),
+                });
+            }
+        }
+
+        overrides.sort_by(|a, b| a.key.cmp(&b.key));
+
+        for flag in flags {
+            let (key, value) = flag
+                .split_once('=')
+                .ok_or_else(|| anyhow!("override must be `key=value`: {flag}"))?;
+
+            overrides.push(Override {
+                key: key.split('.').map(str::to_string).collect(),
+                value: value.to_string(),
+                source: ValueSource::Flag,
+            });
+        }
+
+        Ok(Self(overrides))
+    }
+}
+
+// variables of config tables with single `_`, e.g. `MOMITROLL_DB_HOST` for `MOMITROLL_DB__HOST`,
+// they are ignored, so each is returned with the name it was likely meant to be
+pub fn misspelled_env_vars() -> Vec<(String, String)> {
+    env::vars()
+        .filter_map(|(name, _)| misspelling(&name).map(|fixed| (name, fixed)))
+        .collect()
+}
+
+fn misspelling(name: &str) -> Option<String> {
+    if RESERVED_ENV_VARS.contains(&name) || RUNTIME_ENV_VARS.contains(&name) {
+        return None;
+    }
+
+    let rest = name.strip_prefix(ENV_PREFIX)?;
+
+    if rest.contains("__") {
+        return None;
+    }
+
+    SECTIONS.iter().find_map(|section| {
+        let key = rest.strip_prefix(section)?.strip_prefix('_')?;
+        (!key.is_empty()).then(|| format!("{ENV_PREFIX}{section}__{key}"))
+    })
+}
+
+fn env_var_key(name: &str) -> Option<Vec<String>> {
+    if RESERVED_ENV_VARS.contains(&name) {
+        return None;
+    }
+
+    let parts = name
+        .strip_prefix(ENV_PREFIX)?
+        .split("__")
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>();
+    // names of vars are chosen by users, e.g. `ttl_days`, so they are kept as is
+    let is_var = parts.first().is_some_and(|section| section == "vars");
+    let key = parts
+        .into_iter()
+        .map(|part| match is_var {
+            true => part,
+            false => part.replace('_', "-"),
+        })
+        .collect::<Vec<_>>();
+
+    // single level variables are mostly runtime ones like MOMITROLL_DB_NAME of hooks
+    match key.as_slice() {
+        [_, _, ..] => Some(key),
+        [key] if TOP_LEVEL_KEYS.contains(&key.as_str()) => Some(vec![key.clone()]),
+        _ => None,
+    }
+}
+
+// raw string replaces string values, anything else is parsed like json, e.g. `27018`,
+// `true` or `["base", "demo"]`
+pub(crate) fn apply(root: &mut Value, key: &[String], raw: &str) -> Result<()> {
+    let Some((last, parents)) = key.split_last() else {
+        return Err(anyhow!("override key is empty"));
+    };
+
+    let mut node = root;
+
+    for part in parents {
+        let object = as_object(node, key)?;
+        node = object
+            .entry(part.clone())
+            .or_insert_with(|| Value::Object(Map::new()));
+    }
+
+    let object = as_object(node, key)?;
+    let value = match object.get(last) {
+        Some(Value::String(_)) => Value::String(raw.to_string()),
+        _ => serde_json::from_str(raw).unwrap_or_else(|_| Value::String(raw.to_string())),
+    };
+    object.insert(last.clone(), value);
+
+    Ok(())
+}
+
+fn as_object<'a>(node: &'a mut Value, key: &[String]) -> Result<&'a mut Map<String, Value>> {
+    node.as_object_mut()
+        .ok_or_else(|| anyhow!("can't override `{}`, parent is not a table", key.join(".")))
+}
+
+// dotted paths of scalar values, arrays are taken as a whole
+pub(crate) fn leaves(value: &Value, prefix: &str, paths: &mut Vec<(String, Value)>) {
+    match value {
+        Value::Object(object) => {
+            for (key, value) in object {
+                let path = match prefix.is_empty() {
+                    true => key.clone(),
+                    false => format!("{prefix}.{key}"),
+                };
+                leaves(value, &path, paths);
+            }
+        }
+        value => paths.push((prefix.to_string(), value.clone())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(parts: &[&str]) -> Option<Vec<String>> {
+        Some(parts.iter().map(|part| part.to_string()).collect())
+    }
+
+    #[test]
+    fn env_var_key_splits_levels_on_double_underscore() {
+        assert_eq!(env_var_key("MOMITROLL_DB__HOST"), key(&["db", "host"]));
+        assert_eq!(
+            env_var_key("MOMITROLL_MIGRATION__CHANGELOG_COLL_NAME"),
+            key(&["migration", "changelog-coll-name"])
+        );
+        assert_eq!(
+            env_var_key("MOMITROLL_DB__TLS__CA_FILE"),
+            key(&["db", "tls", "ca-file"])
+        );
+        assert_eq!(env_var_key("MOMITROLL_PRODUCTION"), key(&["production"]));
+        assert_eq!(env_var_key("MOMITROLL_TIMEZONE"), key(&["timezone"]));
+    }
+
+    #[test]
+    fn env_var_key_keeps_names_of_vars() {
+        assert_eq!(
+            env_var_key("MOMITROLL_VARS__TTL_DAYS"),
+            key(&["vars", "ttl_days"])
+        );
+        assert_eq!(
+            env_var_key("MOMITROLL_VARS__MAX-SIZE"),
+            key(&["vars", "max-size"])
+        );
+    }
+
+    #[test]
+    fn env_var_key_skips_reserved_runtime_and_foreign_variables() {
+        assert_eq!(env_var_key("MOMITROLL_ENV"), None);
+        assert_eq!(env_var_key("MOMITROLL_LOG_FILE"), None);
+        assert_eq!(env_var_key("MOMITROLL_DB_NAME"), None);
+        assert_eq!(env_var_key("MOMITROLL_DB_HOST"), None);
+        assert_eq!(env_var_key("DB__HOST"), None);
+    }
+
+    #[test]
+    fn single_underscore_is_reported_with_fixed_name() {
+        assert_eq!(
+            misspelling("MOMITROLL_DB_HOST").as_deref(),
+            Some("MOMITROLL_DB__HOST")
+        );
+        assert_eq!(
+            misspelling("MOMITROLL_MIGRATION_CHANGELOG_COLL_NAME").as_deref(),
+            Some("MOMITROLL_MIGRATION__CHANGELOG_COLL_NAME")
+        );
+        assert_eq!(
+            misspelling("MOMITROLL_CREDS_ENV_VARS_USERNAME").as_deref(),
+            Some("MOMITROLL_CREDS_ENV_VARS__USERNAME")
+        );
+    }
+
+    #[test]
+    fn valid_and_runtime_variables_are_not_reported() {
+        for name in [
+            "MOMITROLL_DB__HOST",
+            "MOMITROLL_DB_NAME",
+            "MOMITROLL_MIGRATION_DIR",
+            "MOMITROLL_LOG_FORMAT",
+            "MOMITROLL_API_URL",
+            "MOMITROLL_DB",
+            "DB_HOST",
+        ] {
+            assert_eq!(misspelling(name), None, "{name}");
+        }
+    }
+
+    #[test]
+    fn overrides_keep_string_type_and_parse_the_rest() {
+        let mut root = serde_json::json!({ "db": { "name": "app", "port": 27017 } });
+        let key = |key: &str| key.split('.').map(str::to_string).collect::<Vec<_>>();
+
+        apply(&mut root, &key("db.name"), "42").unwrap();
+        apply(&mut root, &key("db.port"), "27018").unwrap();
+        apply(&mut root, &key("seed.sets"), r#"["base"]"#).unwrap();
+
+        assert_eq!(
+            root,
+            serde_json::json!({
+                "db": { "name": "42", "port": 27018 },
+                "seed": { "sets": ["base"] },
+            })
+        );
+        assert!(apply(&mut root, &key("db.name.first"), "x").is_err());
+    }
+
+    #[test]
+    fn set_flags_go_after_environment() {
+        let overrides = Overrides::new(&["db.port=27018".to_string()]).unwrap();
+        let last = overrides.0.last().unwrap();
+
+        assert_eq!(last.key, ["db", "port"]);
+        assert_eq!(last.value, "27018");
+        assert!(matches!(last.source, ValueSource::Flag));
+        assert!(Overrides::new(&["db.port".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,158 @@
+use anyhow::Result;
+use serde_json::{Map, Value, json};
+
+#[derive(Clone, Copy)]
+pub enum ConfigFormat {
+    Toml,
+    Json,
+}
+
+impl ConfigFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ConfigFormat::Toml => "toml",
+            ConfigFormat::Json => "json",
+        }
+    }
+
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        match ext {
+            "toml" => Some(ConfigFormat::Toml),
+            "json" => Some(ConfigFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+impl std::str::FromStr for ConfigFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_extension(s).ok_or_else(|| format!("unknown config format: {s}"))
+    }
+}
+
+// values of generated config file, the rest is left to defaults
+pub struct Scaffold {
+    pub migration_dir: String,
+    pub changelog_coll_name: String,
+    pub uri: Option<String>,
+    pub host: String,
+    pub port: u16,
+    pub db_name: String,
+    // names of environment variables with credentials, no auth when missing
+    pub username_env: Option<String>,
+    pub password_env: Option<String>,
+}
+
+impl Default for Scaffold {
+    fn default() -> Self {
+        Self {
+            migration_dir: "migrations".to_string(),
+            changelog_coll_name: "changelog".to_string(),
+            uri: None,
+            host: "localhost".to_string(),
+            port: 27017,
+            db_name: "db".to_string(),
+            username_env: None,
+            password_env: None,
+        }
+    }
+}
+
+impl Scaffold {
+    pub fn render(&self, format: ConfigFormat) -> Result<String> {
+        match format {
+            ConfigFormat::Toml => Ok(self.to_toml()),
+            ConfigFormat::Json => self.to_json(),
+        }
+    }
+
+    fn to_toml(&self) -> String {
+        let quote = |value: &str| toml::Value::String(value.to_string()).to_string();
+        let mut out = String::new();
+
+        out.push_str(
+            "# generated by `momitroll init`, check it with `momitroll config validate`\n\n",
+        );
+        out.push_str("[migration]\n");
+        out.push_str("# directory with migrations, relative to this file\n");
+        out.push_str(&format!("dir = {}\n", quote(&self.migration_dir)));
+        out.push_str("# changelog collection is named with leading underscore\n");
+        out.push_str(&format!(
+            "changelog-coll-name = {}\n",
+            quote(&self.changelog_coll_name)
+        ));
+        out.push_str("# snapshot = true\n\n");
+
+        out.push_str("[db]\n");
+        match &self.uri {
+            Some(uri) => out.push_str(&format!("uri = {}\n", quote(uri))),
+            None => {
+                out.push_str("# uri = \"mongodb+srv://cluster0.example.net/?retryWrites=true\"\n");
+                out.push_str(&format!("host = {}\n", quote(&self.host)));
+                out.push_str(&format!("port = {}\n", self.port));
+            }
+        }
+        out.push_str(&format!("name = {}\n", quote(&self.db_name)));
+
+        match &self.username_env {
+            Some(username) => {
+                out.push_str("\n[credentials]\n");
+                out.push_str("# one of env, file, command, uri, none\n");
+                out.push_str("source = \"env\"\n");
+                out.push_str("# names of environment variables, not the values\n");
+                out.push_str(&format!("username = {}\n", quote(username)));
+                if let Some(password) = &self.password_env {
+                    out.push_str(&format!("password = {}\n", quote(password)));
+                }
+            }
+            None => {
+                out.push_str("\n# [credentials]\n");
+                out.push_str("# source = \"env\"\n");
+                out.push_str("# username = \"MONGO_USERNAME\"\n");
+                out.push_str("# password = \"MONGO_PASSWORD\"\n");
+            }
+        }
+
+        out.push_str("\n# environment profiles are selected with `--env` or MOMITROLL_ENV\n");
+        out.push_str("# [env.prod.db]\n");
+        out.push_str("# name = \"db_prod\"\n");
+
+        out
+    }
+
+    // json can't hold comments, so only values are written
+    fn to_json(&self) -> Result<String> {
+        let mut db = Map::new();
+
+        match &self.uri {
+            Some(uri) => {
+                db.insert("uri".to_string(), json!(uri));
+            }
+            None => {
+                db.insert("host".to_string(), json!(self.host));
+                db.insert("port".to_string(), json!(self.port));
+            }
+        }
+        db.insert("name".to_string(), json!(self.db_name));
+
+        let mut config = json!({
+            "migration": {
+                "dir": self.migration_dir,
+                "changelog-coll-name": self.changelog_coll_name,
+            },
+            "db": Value::Object(db),
+        });
+
+        if let Some(username) = &self.username_env {
+            let mut credentials = json!({ "source": "env", "username": username });
+            if let Some(password) = &self.password_env {
+                credentials["password"] = json!(password);
+            }
+            config["credentials"] = credentials;
+        }
+
+        Ok(serde_json::to_string_pretty(&config)? + "\n")
+    }
+}
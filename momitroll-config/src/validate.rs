@@ -0,0 +1,782 @@
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::{
+    collections::BTreeMap,
+    env,
+    path::{Path, PathBuf},
+};
+
+use crate::{Config, ConfigFile, Credentials, ENVS_KEY, Overrides};
+
+const AUTH_MECHANISMS: [&str; 7] = [
+    "SCRAM-SHA-1",
+    "SCRAM-SHA-256",
+    "MONGODB-X509",
+    "PLAIN",
+    "GSSAPI",
+    "MONGODB-AWS",
+    "MONGODB-OIDC",
+];
+const VALIDATION_LEVELS: [&str; 3] = ["off", "strict", "moderate"];
+const VALIDATION_ACTIONS: [&str; 3] = ["error", "warn", "errorAndLog"];
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Serialize, PartialEq)]
+pub struct Problem {
+    pub severity: Severity,
+    pub key: Option<String>,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+// schema of config file, environment profiles accept the same sections as the root
+pub fn schema() -> Value {
+    let mut schema = serde_json::to_value(schemars::schema_for!(Config)).unwrap_or_default();
+    let properties = schema["properties"].clone();
+
+    schema["properties"][ENVS_KEY] = json!({
+        "description": "environment profiles merged over the root sections",
+        "type": "object",
+        "additionalProperties": {
+            "type": "object",
+            "properties": properties,
+            "additionalProperties": false,
+        },
+    });
+    // editors resolve schema of json config by this key
+    schema["properties"]["$schema"] = json!({ "type": "string" });
+    schema["additionalProperties"] = json!(false);
+
+    schema
+}
+
+pub fn validate(file: &ConfigFile) -> Result<Vec<Problem>> {
+    let (path, is_toml) = match file {
+        ConfigFile::TOML(path) => (path, true),
+        ConfigFile::JSON(path) => (path, false),
+    };
+    let text = std::fs::read_to_string(path)?;
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let mut validator = Validator {
+        problems: vec![],
+        locations: match is_toml {
+            true => toml_locations(&text),
+            false => json_locations(&text),
+        },
+        profile: None,
+    };
+
+    // syntax errors hide everything else
+    let value = match is_toml {
+        true => toml::from_str::<Value>(&text).map_err(|e| toml_error(&text, &e)),
+        false => serde_json::from_str::<Value>(&text).map_err(|e| json_error(&e)),
+    };
+    let value = match value {
+        Ok(value) => value,
+        Err(problem) => return Ok(vec![problem]),
+    };
+
+    let schema = schema();
+    let walker = SchemaWalker { root: &schema };
+    walker.walk(&value, &schema, &mut vec![], &mut validator);
+
+    // typed parsing of the file itself gives location of invalid values
+    let base = match is_toml {
+        true => toml::from_str::<Config>(&text).map_err(|e| toml_error(&text, &e)),
+        false => serde_json::from_str::<Config>(&text).map_err(|e| json_error(&e)),
+    };
+
+    let base_error = match base {
+        Ok(config) => {
+            validator.check_values(&config, dir, None);
+            None
+        }
+        Err(problem) => {
+            let message = problem.message.clone();
+            validator.problems.push(problem);
+            Some(message)
+        }
+    };
+
+    let profiles = value
+        .get(ENVS_KEY)
+        .and_then(Value::as_object)
+        .map(|envs| envs.keys().cloned().collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    for profile in profiles {
+        match Config::from_value(value.clone(), Some(&profile), &Overrides::default()) {
+            Ok(config) => validator.check_values(&config, dir, Some(&profile)),
+            // invalid value of base is already reported at its own location
+            Err(e)
+                if base_error
+                    .as_ref()
+                    .is_some_and(|base| *base == e.to_string()) => {}
+            Err(e) => validator.push(
+                Severity::Error,
+                &format!("{ENVS_KEY}.{profile}"),
+                format!("env profile {profile} is invalid: {e}"),
+            ),
+        }
+    }
+
+    Ok(validator.problems)
+}
+
+struct Validator {
+    problems: Vec<Problem>,
+    locations: BTreeMap<String, (usize, usize)>,
+    // profile which values are checked, its own keys are located first
+    profile: Option<String>,
+}
+
+impl Validator {
+    fn push(&mut self, severity: Severity, key: &str, message: String) {
+        let key = self
+            .profile
+            .as_ref()
+            .map(|profile| format!("{ENVS_KEY}.{profile}.{key}"))
+            .filter(|key| self.locations.contains_key(key))
+            .unwrap_or_else(|| key.to_string());
+        let location = self.locations.get(&key).copied();
+        let problem = Problem {
+            severity,
+            key: Some(key),
+            line: location.map(|(line, _)| line),
+            column: location.map(|(_, column)| column),
+            message,
+        };
+
+        // profiles repeat problems of sections they don't override
+        if !self.problems.contains(&problem) {
+            self.problems.push(problem);
+        }
+    }
+
+    fn check_values(&mut self, config: &Config, dir: &Path, profile: Option<&str>) {
+        use Severity::*;
+
+        self.profile = profile.map(str::to_string);
+        let resolve = |path: &Path| -> PathBuf { dir.join(path) };
+
+        if config.db.port == 0 {
+            self.push(Error, "db.port", "port must be in 1..65535".to_string());
+        }
+        if config.db.name.is_empty() {
+            self.push(Error, "db.name", "database name is empty".to_string());
+        }
+        if let Some(uri) = &config.db.uri
+            && !uri.starts_with("mongodb://")
+            && !uri.starts_with("mongodb+srv://")
+        {
+            self.push(
+                Error,
+                "db.uri",
+                "uri must start with mongodb:// or mongodb+srv://".to_string(),
+            );
+        }
+        if let Some(mechanism) = &config.db.auth_mechanism
+            && !AUTH_MECHANISMS.contains(&mechanism.as_str())
+        {
+            self.push(
+                Error,
+                "db.auth-mechanism",
+                format!(
+                    "unknown auth mechanism `{mechanism}`, expected one of: {}",
+                    AUTH_MECHANISMS.join(", ")
+                ),
+            );
+        }
+        if let Some(tls) = &config.db.tls {
+            for (key, file) in [
+                ("db.tls.ca-file", &tls.ca_file),
+                ("db.tls.cert-key-file", &tls.cert_key_file),
+            ] {
+                if let Some(file) = file
+                    && !resolve(file).is_file()
+                {
+                    self.push(
+                        Error,
+                        key,
+                        format!("file doesn't exist: {}", file.display()),
+                    );
+                }
+            }
+        }
+
+        if !resolve(Path::new(&config.migration.dir)).is_dir() {
+            self.push(
+                Error,
+                "migration.dir",
+                format!(
+                    "migration directory doesn't exist: {}",
+                    config.migration.dir
+                ),
+            );
+        }
+        if config.migration.changelog_coll_name.is_empty() {
+            self.push(
+                Error,
+                "migration.changelog-coll-name",
+                "changelog collection name is empty".to_string(),
+            );
+        }
+        if config.migration.data_batch_size == 0 {
+            self.push(
+                Error,
+                "migration.data-batch-size",
+                "batch size must be positive".to_string(),
+            );
+        }
+        if config.migration.history_ttl_days == Some(0) {
+            self.push(
+                Error,
+                "migration.history-ttl-days",
+                "history ttl must be positive, remove it to keep history forever".to_string(),
+            );
+        }
+        if !config.seed.sets.is_empty() && !resolve(Path::new(&config.seed.dir)).is_dir() {
+            self.push(
+                Error,
+                "seed.dir",
+                format!("seed directory doesn't exist: {}", config.seed.dir),
+            );
+        }
+        if !VALIDATION_LEVELS.contains(&config.validators.validation_level.as_str()) {
+            self.push(
+                Error,
+                "validators.validation-level",
+                format!(
+                    "validation level must be one of: {}",
+                    VALIDATION_LEVELS.join(", ")
+                ),
+            );
+        }
+        if !VALIDATION_ACTIONS.contains(&config.validators.validation_action.as_str()) {
+            self.push(
+                Error,
+                "validators.validation-action",
+                format!(
+                    "validation action must be one of: {}",
+                    VALIDATION_ACTIONS.join(", ")
+                ),
+            );
+        }
+
+        if let Err(e) = config.timezone() {
+            self.push(Error, "timezone", e.to_string());
+        }
+
+        let hooks = &config.hooks;
+        for (point, hooks) in [
+            ("before-up", &hooks.before_up),
+            ("after-up", &hooks.after_up),
+            ("before-each", &hooks.before_each),
+            ("after-each", &hooks.after_each),
+            ("on-failure", &hooks.on_failure),
+        ] {
+            for (i, hook) in hooks.iter().enumerate() {
+                if hook.command.is_none() && hook.migration.is_none() {
+                    self.push(
+                        Error,
+                        &format!("hooks.{point}.{i}"),
+                        "hook needs `command` or `migration`".to_string(),
+                    );
+                }
+            }
+        }
+
+        self.check_credentials(config, dir);
+    }
+
+    // missing variables are warnings, they are often set only on deploy
+    fn check_credentials(&mut self, config: &Config, dir: &Path) {
+        let env_vars = match (&config.credentials, &config.creds_env_vars) {
+            (Some(_), Some(_)) => {
+                self.push(
+                    Severity::Error,
+                    "credentials",
+                    "`credentials` and `creds-env-vars` can't be used together".to_string(),
+                );
+                return;
+            }
+            (Some(Credentials::Env { username, password }), _) => {
+                Some(("credentials", username, password))
+            }
+            (None, Some(vars)) => Some(("creds-env-vars", &vars.username, &vars.password)),
+            (
+                Some(Credentials::File {
+                    username_file,
+                    password_file,
+                    ..
+                }),
+                _,
+            ) => {
+                for (key, file) in [
+                    ("credentials.username-file", username_file.as_ref()),
+                    ("credentials.password-file", Some(password_file)),
+                ] {
+                    if let Some(file) = file
+                        && !dir.join(file).is_file()
+                    {
+                        self.push(
+                            Severity::Error,
+                            key,
+                            format!("file doesn't exist: {}", file.display()),
+                        );
+                    }
+                }
+                None
+            }
+            (Some(Credentials::Uri), _) if config.db.uri.is_none() => {
+                self.push(
+                    Severity::Error,
+                    "credentials.source",
+                    "credentials source `uri` requires `db.uri`".to_string(),
+                );
+                None
+            }
+            _ => None,
+        };
+
+        if let Some((section, username, password)) = env_vars {
+            for (key, var) in [
+                ("username", Some(username)),
+                ("password", password.as_ref()),
+            ] {
+                if let Some(var) = var
+                    && env::var_os(var).is_none()
+                {
+                    self.push(
+                        Severity::Warning,
+                        &format!("{section}.{key}"),
+                        format!("environment variable {var} is not set"),
+                    );
+                }
+            }
+        }
+    }
+}
+
+struct SchemaWalker<'a> {
+    root: &'a Value,
+}
+
+impl<'a> SchemaWalker<'a> {
+    fn walk(
+        &self,
+        value: &Value,
+        schema: &'a Value,
+        path: &mut Vec<String>,
+        validator: &mut Validator,
+    ) {
+        match value {
+            Value::Object(object) => {
+                for (key, child) in object {
+                    // aliases of kebab-case keys use underscores
+                    let child_schema = self
+                        .property(schema, key)
+                        .or_else(|| self.property(schema, &key.replace('_', "-")))
+                        .or_else(|| self.additional(schema));
+
+                    path.push(key.clone());
+
+                    match child_schema {
+                        Some(child_schema) => self.walk(child, child_schema, path, validator),
+                        // misspelled key is likely meant to be the suggested one, so its
+                        // content is checked against that one, e.g. `cred-env-vars.passowrd`
+                        None => match suggest(key, &self.keys(schema)) {
+                            Some(known) => {
+                                validator.push(
+                                    Severity::Error,
+                                    &path.join("."),
+                                    format!("unknown key `{key}`, did you mean `{known}`?"),
+                                );
+
+                                if let Some(known_schema) = self.property(schema, known) {
+                                    self.walk(child, known_schema, path, validator);
+                                }
+                            }
+                            None => validator.push(
+                                Severity::Error,
+                                &path.join("."),
+                                format!("unknown key `{key}`"),
+                            ),
+                        },
+                    }
+
+                    path.pop();
+                }
+            }
+            Value::Array(items) => {
+                if let Some(schema) = self.items(schema) {
+                    for (i, item) in items.iter().enumerate() {
+                        path.push(i.to_string());
+                        self.walk(item, schema, path, validator);
+                        path.pop();
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // `$ref` is resolved, `Option` and enums are flattened into their variants
+    fn variants(&self, schema: &'a Value) -> Vec<&'a Value> {
+        let schema = match schema.get("$ref").and_then(Value::as_str) {
+            Some(reference) => reference
+                .strip_prefix("#/$defs/")
+                .and_then(|name| self.root.get("$defs")?.get(name))
+                .unwrap_or(schema),
+            None => schema,
+        };
+
+        match ["anyOf", "oneOf", "allOf"]
+            .iter()
+            .find_map(|key| schema.get(*key).and_then(Value::as_array))
+        {
+            Some(variants) => variants.iter().flat_map(|v| self.variants(v)).collect(),
+            None => vec![schema],
+        }
+    }
+
+    fn property(&self, schema: &'a Value, key: &str) -> Option<&'a Value> {
+        self.variants(schema)
+            .into_iter()
+            .find_map(|variant| variant.get("properties")?.get(key))
+    }
+
+    // schema of map values, e.g. `vars`
+    fn additional(&self, schema: &'a Value) -> Option<&'a Value> {
+        self.variants(schema).into_iter().find_map(|variant| {
+            variant
+                .get("additionalProperties")
+                .filter(|v| v.is_object())
+        })
+    }
+
+    fn items(&self, schema: &'a Value) -> Option<&'a Value> {
+        self.variants(schema)
+            .into_iter()
+            .find_map(|variant| variant.get("items"))
+    }
+
+    fn keys(&self, schema: &'a Value) -> Vec<&'a str> {
+        self.variants(schema)
+            .into_iter()
+            .filter_map(|variant| variant.get("properties")?.as_object())
+            .flat_map(|properties| properties.keys().map(String::as_str))
+            .collect()
+    }
+}
+
+fn suggest<'a>(key: &str, known: &[&'a str]) -> Option<&'a str> {
+    known
+        .iter()
+        .map(|candidate| (strsim::jaro_winkler(key, candidate), *candidate))
+        .filter(|(score, _)| *score > 0.8)
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, candidate)| candidate)
+}
+
+fn line_column(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.chars().rev().take_while(|c| *c != '\n').count() + 1;
+
+    (line, column)
+}
+
+fn toml_error(text: &str, error: &toml::de::Error) -> Problem {
+    let location = error.span().map(|span| line_column(text, span.start));
+
+    Problem {
+        severity: Severity::Error,
+        key: None,
+        line: location.map(|(line, _)| line),
+        column: location.map(|(_, column)| column),
+        message: error.message().to_string(),
+    }
+}
+
+// location is reported separately, so it's cut from the message
+fn json_error(error: &serde_json::Error) -> Problem {
+    let message = error.to_string();
+    let location = format!(" at line {} column {}", error.line(), error.column());
+
+    Problem {
+        severity: Severity::Error,
+        key: None,
+        line: Some(error.line()),
+        column: Some(error.column()),
+        message: message
+            .strip_suffix(&location)
+            .unwrap_or(&message)
+            .to_string(),
+    }
+}
+
+// positions of keys by dotted path, array items are addressed by index
+fn toml_locations(text: &str) -> BTreeMap<String, (usize, usize)> {
+    use toml::de::{DeTable, DeValue};
+
+    fn walk(
+        table: &DeTable,
+        text: &str,
+        path: &mut Vec<String>,
+        locations: &mut BTreeMap<String, (usize, usize)>,
+    ) {
+        for (key, value) in table.iter() {
+            path.push(key.get_ref().to_string());
+            locations
+                .entry(path.join("."))
+                .or_insert_with(|| line_column(text, key.span().start));
+
+            match value.get_ref() {
+                DeValue::Table(table) => walk(table, text, path, locations),
+                DeValue::Array(items) => {
+                    for (i, item) in items.iter().enumerate() {
+                        if let DeValue::Table(table) = item.get_ref() {
+                            path.push(i.to_string());
+                            walk(table, text, path, locations);
+                            path.pop();
+                        }
+                    }
+                }
+                _ => {}
+            }
+
+            path.pop();
+        }
+    }
+
+    let mut locations = BTreeMap::new();
+
+    if let Ok(table) = DeTable::parse(text) {
+        walk(table.get_ref(), text, &mut vec![], &mut locations);
+    }
+
+    locations
+}
+
+fn json_locations(text: &str) -> BTreeMap<String, (usize, usize)> {
+    enum Frame {
+        Object {
+            key: Option<String>,
+            expects_key: bool,
+        },
+        Array {
+            index: usize,
+        },
+    }
+
+    let mut locations = BTreeMap::new();
+    let mut stack: Vec<Frame> = vec![];
+    let mut chars = text.char_indices();
+
+    while let Some((offset, c)) = chars.next() {
+        match c {
+            '{' => stack.push(Frame::Object {
+                key: None,
+                expects_key: true,
+            }),
+            '[' => stack.push(Frame::Array { index: 0 }),
+            '}' | ']' => {
+                stack.pop();
+            }
+            ',' => match stack.last_mut() {
+                Some(Frame::Object { expects_key, .. }) => *expects_key = true,
+                Some(Frame::Array { index }) => *index += 1,
+                None => {}
+            },
+            ':' => {
+                if let Some(Frame::Object { expects_key, .. }) = stack.last_mut() {
+                    *expects_key = false;
+                }
+            }
+            '"' => {
+                let mut string = String::new();
+                let mut escaped = false;
+
+                for (_, c) in chars.by_ref() {
+                    match (escaped, c) {
+                        (false, '\\') => escaped = true,
+                        (false, '"') => break,
+                        (_, c) => {
+                            escaped = false;
+                            string.push(c);
+                        }
+                    }
+                }
+
+                if let Some(Frame::Object {
+                    key,
+                    expects_key: true,
+                }) = stack.last_mut()
+                {
+                    *key = Some(string);
+
+                    let path = stack
+                        .iter()
+                        .map(|frame| match frame {
+                            Frame::Object { key, .. } => key.clone().unwrap_or_default(),
+                            Frame::Array { index } => index.to_string(),
+                        })
+                        .collect::<Vec<_>>()
+                        .join(".");
+                    locations
+                        .entry(path)
+                        .or_insert_with(|| line_column(text, offset));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    locations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOML: &str = r#"[migration]
+dir = "."
+changelog-coll-name = "changelog"
+
+[db]
+name = "app"
+port = 70000
+
+[cred-env-vars]
+passowrd = "P"
+
+[env.prod.db]
+name = "prod"
+"#;
+
+    const JSON: &str = r#"{
+  "migration": { "dir": ".", "changelog-coll-name": "changelog" },
+  "db": { "name": "app", "port": 70000 },
+  "cred-env-vars": { "passowrd": "P" },
+  "env": { "prod": { "db": { "name": "prod" } } }
+}"#;
+
+    fn problems(file: ConfigFile, text: &str) -> Vec<(Option<String>, usize, usize, String)> {
+        let path = match &file {
+            ConfigFile::TOML(path) | ConfigFile::JSON(path) => path.clone(),
+        };
+        std::fs::write(&path, text).unwrap();
+        let problems = validate(&file);
+        std::fs::remove_file(&path).unwrap();
+
+        problems
+            .unwrap()
+            .into_iter()
+            .map(|p| (p.key, p.line.unwrap(), p.column.unwrap(), p.message))
+            .collect()
+    }
+
+    fn temp_file(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("momitroll-{}-{name}", std::process::id()))
+    }
+
+    #[test]
+    fn suggest_picks_closest_known_key() {
+        let known = ["username", "password", "source"];
+
+        assert_eq!(suggest("passowrd", &known), Some("password"));
+        assert_eq!(suggest("usrname", &known), Some("username"));
+        assert_eq!(suggest("timeout", &known), None);
+    }
+
+    #[test]
+    fn toml_locations_cover_nested_keys_and_arrays() {
+        let locations = toml_locations(
+            "[db]\nname = \"app\"\n\n[[hooks.before-up]]\ncommand = \"true\"\n\n[env.prod.db]\n  port = 1\n",
+        );
+
+        assert_eq!(locations["db"], (1, 2));
+        assert_eq!(locations["db.name"], (2, 1));
+        assert_eq!(locations["hooks.before-up.0.command"], (5, 1));
+        assert_eq!(locations["env.prod.db.port"], (8, 3));
+    }
+
+    #[test]
+    fn json_locations_cover_nested_keys_and_arrays() {
+        let locations = json_locations(
+            "{\n  \"db\": { \"name\": \"a\\\"}\" },\n  \"hooks\": { \"after-up\": [{}, { \"command\": \"x\" }] }\n}",
+        );
+
+        assert_eq!(locations["db"], (2, 3));
+        assert_eq!(locations["db.name"], (2, 11));
+        assert_eq!(locations["hooks.after-up.1.command"], (3, 33));
+    }
+
+    #[test]
+    fn toml_problems_are_located_and_reported_once() {
+        assert_eq!(
+            problems(ConfigFile::TOML(temp_file("config.toml")), TOML),
+            [
+                (
+                    Some("cred-env-vars".to_string()),
+                    9,
+                    2,
+                    "unknown key `cred-env-vars`, did you mean `creds-env-vars`?".to_string()
+                ),
+                (
+                    Some("cred-env-vars.passowrd".to_string()),
+                    10,
+                    1,
+                    "unknown key `passowrd`, did you mean `password`?".to_string()
+                ),
+                (
+                    None,
+                    7,
+                    8,
+                    "invalid value: integer `70000`, expected u16".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn json_problems_are_located_and_reported_once() {
+        let problems = problems(ConfigFile::JSON(temp_file("config.json")), JSON);
+
+        assert_eq!(
+            problems[..2],
+            [
+                (
+                    Some("cred-env-vars".to_string()),
+                    4,
+                    3,
+                    "unknown key `cred-env-vars`, did you mean `creds-env-vars`?".to_string()
+                ),
+                (
+                    Some("cred-env-vars.passowrd".to_string()),
+                    4,
+                    22,
+                    "unknown key `passowrd`, did you mean `password`?".to_string()
+                ),
+            ]
+        );
+        assert_eq!(problems.len(), 3);
+        assert_eq!(
+            (
+                problems[2].0.as_deref(),
+                problems[2].1,
+                problems[2].3.as_str()
+            ),
+            (None, 3, "invalid value: integer `70000`, expected u16")
+        );
+    }
+}
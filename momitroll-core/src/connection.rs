@@ -0,0 +1,93 @@
+use anyhow::{Context, Result};
+use mongodb::{
+    Client, Database,
+    options::{AuthMechanism, ClientOptions, Credential, Tls, TlsOptions},
+};
+use tracing::debug;
+
+use momitroll_config::{Config, Credentials};
+use momitroll_util::{
+    db::{helthcheck, redact_uri},
+    error::ErrorKind,
+};
+
+pub(crate) async fn connect(config: &Config) -> Result<Database> {
+    let options = client_options(config).await.context(ErrorKind::Config)?;
+
+    debug!(
+        "connecting to {}",
+        redact_uri(&config.db.connection_string())
+    );
+
+    let db = Client::with_options(options)
+        .context(ErrorKind::Connection)?
+        .database(&config.db.name);
+
+    helthcheck(&db).await.context(ErrorKind::Connection)?;
+
+    Ok(db)
+}
+
+// structured fields of config override options of connection string
+async fn client_options(config: &Config) -> Result<ClientOptions> {
+    let db = &config.db;
+    let uri = db.connection_string();
+    let mut options = ClientOptions::parse(&uri)
+        .await
+        .with_context(|| format!("invalid connection string: {}", redact_uri(&uri)))?;
+
+    if let Some(replica_set) = &db.replica_set {
+        options.repl_set_name = Some(replica_set.clone());
+    }
+
+    if let Some(tls) = &db.tls {
+        options.tls = Some(match tls.enabled {
+            true => Tls::Enabled(
+                TlsOptions::builder()
+                    .ca_file_path(tls.ca_file.clone())
+                    .cert_key_file_path(tls.cert_key_file.clone())
+                    .allow_invalid_certificates(tls.allow_invalid_certificates)
+                    .build(),
+            ),
+            false => Tls::Disabled,
+        });
+    }
+
+    // no-auth mode ignores credentials of connection string as well
+    if matches!(config.credentials, Some(Credentials::None)) {
+        options.credential = None;
+        return Ok(options);
+    }
+
+    let creds = config.resolve_credentials()?;
+
+    if creds.is_none() && db.auth_source.is_none() && db.auth_mechanism.is_none() {
+        return Ok(options);
+    }
+
+    let credential = options.credential.get_or_insert_with(Credential::default);
+
+    if let Some((username, password)) = creds {
+        credential.username = Some(username);
+        credential.password = password;
+    }
+
+    // keeps previous behavior of structured config
+    if db.uri.is_none() && credential.source.is_none() {
+        credential.source = Some("admin".to_string());
+    }
+
+    if let Some(source) = &db.auth_source {
+        credential.source = Some(source.clone());
+    }
+
+    if let Some(mechanism) = &db.auth_mechanism {
+        credential.mechanism = Some(
+            mechanism
+                .parse::<AuthMechanism>()
+                .with_context(|| format!("unsupported auth mechanism: {mechanism}"))?,
+        );
+    }
+
+    Ok(options)
+}
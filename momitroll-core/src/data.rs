@@ -0,0 +1,356 @@
+use anyhow::{Context, Result, anyhow};
+use bson::{Bson, Document};
+use mongodb::Database;
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+};
+use tracing::info;
+
+use crate::migration::MigrationController;
+
+pub const LOAD_DATA_COMMAND: &str = "$loadData";
+
+#[derive(Clone, Copy)]
+pub enum DataFormat {
+    Ndjson,
+    JsonArray,
+    Bson,
+}
+
+impl DataFormat {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        match path.as_ref().extension().and_then(|ext| ext.to_str()) {
+            Some("ndjson" | "jsonl") => Ok(DataFormat::Ndjson),
+            Some("json") => Ok(DataFormat::JsonArray),
+            Some("bson") => Ok(DataFormat::Bson),
+            _ => Err(anyhow!(
+                "unsupported data file: {}, expected .ndjson, .jsonl, .json or .bson",
+                path.as_ref().display()
+            )),
+        }
+    }
+}
+
+// `{ "$loadData": { "collection": "users", "file": "users.ndjson", "batchSize": 1000 } }`
+pub struct LoadData {
+    pub collection: String,
+    pub file: PathBuf,
+    pub batch_size: Option<usize>,
+}
+
+impl LoadData {
+    pub fn from_command(command: &Document, base_dir: &Path) -> Result<Option<Self>> {
+        let Some(spec) = command.get(LOAD_DATA_COMMAND) else {
+            return Ok(None);
+        };
+        let spec = spec
+            .as_document()
+            .ok_or_else(|| anyhow!("{LOAD_DATA_COMMAND} must be an object"))?;
+        let batch_size = match spec.get("batchSize") {
+            Some(Bson::Int32(size)) if *size > 0 => Some(*size as usize),
+            Some(Bson::Int64(size)) if *size > 0 => Some(*size as usize),
+            Some(_) => {
+                return Err(anyhow!(
+                    "{LOAD_DATA_COMMAND}.batchSize must be a positive integer"
+                ));
+            }
+            None => None,
+        };
+
+        let file = spec
+            .get_str("file")
+            .with_context(|| format!("{LOAD_DATA_COMMAND}.file must be a string"))?;
+
+        Ok(Some(Self {
+            collection: spec
+                .get_str("collection")
+                .with_context(|| format!("{LOAD_DATA_COMMAND}.collection must be a string"))?
+                .to_string(),
+            file: contained_file(base_dir, file)?,
+            batch_size,
+        }))
+    }
+}
+
+// data files are read and inlined by `export`, so they can't point outside of directory
+// of migration or seed set
+pub(crate) fn contained_file(base_dir: &Path, file: &str) -> Result<PathBuf> {
+    let dir = base_dir
+        .canonicalize()
+        .with_context(|| format!("failed to resolve {}", base_dir.display()))?;
+    let path = dir
+        .join(file)
+        .canonicalize()
+        .with_context(|| format!("data file {file} doesn't exist in {}", base_dir.display()))?;
+
+    match path.starts_with(&dir) {
+        true => Ok(path),
+        false => Err(anyhow!(
+            "data file {file} must be inside {}",
+            base_dir.display()
+        )),
+    }
+}
+
+// every format is streamed, json arrays are split into elements without parsing whole file
+pub enum DataReader {
+    Ndjson(std::io::Lines<BufReader<File>>),
+    JsonArray(JsonArrayReader),
+    Bson(BufReader<File>),
+}
+
+impl DataReader {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .with_context(|| format!("failed to open data file {}", path.display()))?;
+
+        Ok(match DataFormat::from_path(path)? {
+            DataFormat::Ndjson => DataReader::Ndjson(BufReader::new(file).lines()),
+            DataFormat::JsonArray => {
+                DataReader::JsonArray(JsonArrayReader::new(BufReader::new(file)))
+            }
+            DataFormat::Bson => DataReader::Bson(BufReader::new(file)),
+        })
+    }
+
+    fn to_document(value: serde_json::Value) -> Result<Document> {
+        match Bson::try_from(value)? {
+            Bson::Document(doc) => Ok(doc),
+            _ => Err(anyhow!("each data entry must be an object")),
+        }
+    }
+}
+
+impl Iterator for DataReader {
+    type Item = Result<Document>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            DataReader::Ndjson(lines) => loop {
+                match lines.next()? {
+                    Ok(line) if line.trim().is_empty() => continue,
+                    Ok(line) => {
+                        return Some(
+                            serde_json::from_str(&line)
+                                .map_err(anyhow::Error::from)
+                                .and_then(Self::to_document),
+                        );
+                    }
+                    Err(e) => return Some(Err(e.into())),
+                }
+            },
+            DataReader::JsonArray(reader) => reader.next_element().transpose().map(|element| {
+                element.and_then(|element| Self::to_document(serde_json::from_slice(&element)?))
+            }),
+            DataReader::Bson(reader) => match reader.fill_buf() {
+                Ok([]) => None,
+                Ok(_) => Some(Document::from_reader(reader).map_err(anyhow::Error::from)),
+                Err(e) => Some(Err(e.into())),
+            },
+        }
+    }
+}
+
+pub struct JsonArrayReader<R = BufReader<File>> {
+    bytes: std::io::Bytes<R>,
+    started: bool,
+    done: bool,
+    count: usize,
+}
+
+impl<R: BufRead> JsonArrayReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            bytes: reader.bytes(),
+            started: false,
+            done: false,
+            count: 0,
+        }
+    }
+
+    // raw bytes of next top level element, brackets inside strings are skipped,
+    // empty or whitespace-only file has no elements
+    fn next_element(&mut self) -> Result<Option<Vec<u8>>> {
+        if self.done {
+            return Ok(None);
+        }
+
+        if !self.started {
+            match self.next_byte()? {
+                Some(b'[') => self.started = true,
+                Some(_) => return Err(anyhow!("json data file must be an array")),
+                None => {
+                    self.done = true;
+                    return Ok(None);
+                }
+            }
+        }
+
+        let mut element = vec![];
+        let mut depth = 0usize;
+        let mut in_string = false;
+        let mut escaped = false;
+
+        loop {
+            let byte = self
+                .bytes
+                .next()
+                .transpose()?
+                .ok_or_else(|| anyhow!("unterminated json array in data file"))?;
+
+            if in_string {
+                match byte {
+                    _ if escaped => escaped = false,
+                    b'\\' => escaped = true,
+                    b'"' => in_string = false,
+                    _ => {}
+                }
+                element.push(byte);
+                continue;
+            }
+
+            match byte {
+                b',' | b']' if depth == 0 => {
+                    self.done = byte == b']';
+
+                    if self.done && self.next_byte()?.is_some() {
+                        return Err(anyhow!("unexpected data after json array in data file"));
+                    }
+
+                    return match (element.is_empty(), self.done && self.count == 0) {
+                        (false, _) => {
+                            self.count += 1;
+                            Ok(Some(element))
+                        }
+                        (true, true) => Ok(None),
+                        (true, false) => Err(anyhow!("empty element in json array of data file")),
+                    };
+                }
+                _ if depth == 0 && byte.is_ascii_whitespace() => {}
+                b'{' | b'[' => depth += 1,
+                b'}' | b']' => depth = depth.saturating_sub(1),
+                b'"' => in_string = true,
+                _ => {}
+            }
+
+            if depth > 0 || !byte.is_ascii_whitespace() {
+                element.push(byte);
+            }
+        }
+    }
+
+    fn next_byte(&mut self) -> Result<Option<u8>> {
+        for byte in self.bytes.by_ref() {
+            let byte = byte?;
+
+            if !byte.is_ascii_whitespace() {
+                return Ok(Some(byte));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+impl MigrationController {
+    pub(crate) async fn load_data(&self, load: &LoadData) -> Result<()> {
+        insert_data(&self.db, load, self.config.migration.data_batch_size).await
+    }
+}
+
+// shared with javascript migrations, which run `$loadData` while script runs
+pub(crate) async fn insert_data(
+    db: &Database,
+    load: &LoadData,
+    default_batch_size: usize,
+) -> Result<()> {
+    let batch_size = load.batch_size.unwrap_or(default_batch_size);
+    let collection = db.collection::<Document>(&load.collection);
+    let mut batch = Vec::with_capacity(batch_size);
+    let mut total = 0;
+
+    for doc in DataReader::open(&load.file)? {
+        batch.push(doc?);
+
+        if batch.len() >= batch_size {
+            total += batch.len();
+            collection.insert_many(std::mem::take(&mut batch)).await?;
+        }
+    }
+
+    if !batch.is_empty() {
+        total += batch.len();
+        collection.insert_many(batch).await?;
+    }
+
+    info!(
+        "loaded {total} documents into {} from {}",
+        load.collection,
+        load.file.display()
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // tiny buffer splits elements between reads
+    fn elements(source: &str) -> Result<Vec<String>> {
+        let mut reader = JsonArrayReader::new(BufReader::with_capacity(3, source.as_bytes()));
+        let mut res = vec![];
+
+        while let Some(element) = reader.next_element()? {
+            res.push(String::from_utf8(element)?);
+        }
+
+        Ok(res)
+    }
+
+    #[test]
+    fn json_array_is_split_into_elements() {
+        assert_eq!(
+            elements(" [ {\"a\": 1} ,\n{\"b\": [2, 3]} ] ").unwrap(),
+            [r#"{"a": 1}"#, r#"{"b": [2, 3]}"#]
+        );
+    }
+
+    #[test]
+    fn json_array_keeps_nested_values_and_strings() {
+        assert_eq!(
+            elements(r#"[{"a": {"b": [{"c": []}]}}, [1, [2]], {"s": "x\"],[{\\"}]"#).unwrap(),
+            [
+                r#"{"a": {"b": [{"c": []}]}}"#,
+                "[1, [2]]",
+                r#"{"s": "x\"],[{\\"}"#
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_json_array_has_no_elements() {
+        assert!(elements("[]").unwrap().is_empty());
+        assert!(elements(" [ \n ] ").unwrap().is_empty());
+        assert!(elements("").unwrap().is_empty());
+        assert!(elements(" \n\t ").unwrap().is_empty());
+    }
+
+    #[test]
+    fn malformed_json_array_is_error() {
+        for source in [
+            "[{\"a\": 1},]",
+            "[,]",
+            "[{\"a\": 1},,{\"b\": 2}]",
+            "[{\"a\": 1}",
+            "[{\"a\": \"]",
+            "{\"a\": 1}",
+            "[1] [2]",
+        ] {
+            assert!(elements(source).is_err(), "{source}");
+        }
+    }
+}
@@ -0,0 +1,433 @@
+use anyhow::{Context, Result};
+use bson::{Document, doc};
+use mongodb::Database;
+use tracing::{info, warn};
+
+use crate::{
+    connection::connect,
+    ds::MigrationContent,
+    indexes::{IndexChange, diff_indexes},
+    migration::{MigrationController, internal_collections},
+    report::{Change, DiffReport, SchemaChangeEntry},
+    schema::{CollectionKind, CollectionSchema, SchemaSnapshot, is_equivalent},
+};
+use momitroll_config::{Config, Overrides};
+use momitroll_util::{db::connect_uri, error::ErrorKind};
+
+// options which can be changed in place with `collMod`
+const COLLECTION_MOD_OPTIONS: [&str; 3] = ["validator", "validationLevel", "validationAction"];
+const VIEW_MOD_OPTIONS: [&str; 2] = ["viewOn", "pipeline"];
+
+pub enum SchemaChange<'a> {
+    // exists only in source
+    Missing(&'a CollectionSchema),
+    // exists only in target
+    Extra(&'a CollectionSchema),
+    Changed {
+        source: &'a CollectionSchema,
+        target: &'a CollectionSchema,
+        options: Vec<String>,
+    },
+    Index(IndexChange),
+}
+
+impl SchemaChange<'_> {
+    fn up_commands(&self) -> Vec<Document> {
+        match self {
+            SchemaChange::Missing(source) => source.create_commands(),
+            SchemaChange::Extra(target) => vec![doc! { "drop": &target.name }],
+            SchemaChange::Changed { source, .. } => mod_command(source).into_iter().collect(),
+            SchemaChange::Index(change) => change.up_commands(),
+        }
+    }
+
+    fn down_commands(&self) -> Vec<Document> {
+        match self {
+            SchemaChange::Missing(source) => vec![doc! { "drop": &source.name }],
+            SchemaChange::Extra(target) => target.create_commands(),
+            SchemaChange::Changed { target, .. } => mod_command(target).into_iter().collect(),
+            SchemaChange::Index(change) => change.down_commands(),
+        }
+    }
+}
+
+fn mod_options(kind: CollectionKind) -> &'static [&'static str] {
+    match kind {
+        CollectionKind::Collection => &COLLECTION_MOD_OPTIONS,
+        CollectionKind::View => &VIEW_MOD_OPTIONS,
+    }
+}
+
+fn mod_command(collection: &CollectionSchema) -> Option<Document> {
+    let mut command = doc! { "collMod": &collection.name };
+
+    for option in mod_options(collection.kind) {
+        match collection.options.get(*option) {
+            Some(value) => command.insert(*option, value.clone()),
+            // removes validator, which doesn't exist on the other side
+            None if *option == "validator" => command.insert(*option, Document::new()),
+            None => None,
+        };
+    }
+
+    (command.len() > 1).then_some(command)
+}
+
+fn changed_options(source: &CollectionSchema, target: &CollectionSchema) -> Vec<String> {
+    let mut keys = source.options.keys().cloned().collect::<Vec<_>>();
+
+    for key in target.options.keys() {
+        if !keys.contains(key) {
+            keys.push(key.clone());
+        }
+    }
+
+    keys.into_iter()
+        .filter(
+            |key| match (source.options.get(key), target.options.get(key)) {
+                (Some(a), Some(b)) => !is_equivalent(a, b),
+                _ => true,
+            },
+        )
+        .collect()
+}
+
+pub fn diff_schemas<'a>(
+    source: &'a SchemaSnapshot,
+    target: &'a SchemaSnapshot,
+) -> Vec<SchemaChange<'a>> {
+    let mut changes = vec![];
+
+    for source_coll in &source.collections {
+        let Some(target_coll) = target
+            .collections
+            .iter()
+            .find(|c| c.name == source_coll.name)
+        else {
+            changes.push(SchemaChange::Missing(source_coll));
+            continue;
+        };
+
+        // collection replaced by view or vice versa can't be modified in place
+        if source_coll.kind != target_coll.kind {
+            changes.push(SchemaChange::Extra(target_coll));
+            changes.push(SchemaChange::Missing(source_coll));
+            continue;
+        }
+
+        let options = changed_options(source_coll, target_coll);
+
+        if !options.is_empty() {
+            changes.push(SchemaChange::Changed {
+                source: source_coll,
+                target: target_coll,
+                options,
+            });
+        }
+
+        changes.extend(
+            diff_indexes(
+                &source_coll.name,
+                &target_coll.indexes,
+                &source_coll.indexes,
+            )
+            .into_iter()
+            .map(SchemaChange::Index),
+        );
+    }
+
+    for target_coll in &target.collections {
+        if !source
+            .collections
+            .iter()
+            .any(|c| c.name == target_coll.name)
+        {
+            changes.push(SchemaChange::Extra(target_coll));
+        }
+    }
+
+    changes
+}
+
+// schemas of both databases, database of config is connected only to generate migration
+pub struct SchemaDiff {
+    source: SchemaSnapshot,
+    target: SchemaSnapshot,
+}
+
+impl SchemaDiff {
+    pub async fn capture(
+        config: &Config,
+        overrides: &Overrides,
+        source: &str,
+        target: &str,
+    ) -> Result<Self> {
+        let exclude = internal_collections(config);
+        let source_db = connect_to(config, overrides, source).await?;
+        let target_db = connect_to(config, overrides, target).await?;
+
+        Ok(Self {
+            source: SchemaSnapshot::capture(&source_db, &exclude).await?,
+            target: SchemaSnapshot::capture(&target_db, &exclude).await?,
+        })
+    }
+
+    pub fn changes(&self) -> Vec<SchemaChange<'_>> {
+        diff_schemas(&self.source, &self.target)
+    }
+
+    pub fn report(&self) -> DiffReport {
+        let changes = self.changes();
+
+        if changes.is_empty() {
+            info!("databases have the same schema");
+        }
+
+        DiffReport {
+            changes: changes.iter().map(change_entry).collect(),
+        }
+    }
+}
+
+fn change_entry(change: &SchemaChange) -> SchemaChangeEntry {
+    let entry = |collection: &CollectionSchema, change| SchemaChangeEntry {
+        kind: kind_name(collection.kind),
+        name: collection.name.clone(),
+        change,
+        options: vec![],
+    };
+
+    match change {
+        SchemaChange::Missing(c) => entry(c, Change::Missing),
+        SchemaChange::Extra(c) => entry(c, Change::Extra),
+        SchemaChange::Changed {
+            source, options, ..
+        } => {
+            let unmodifiable = options
+                .iter()
+                .filter(|option| !mod_options(source.kind).contains(&option.as_str()))
+                .map(String::as_str)
+                .collect::<Vec<_>>();
+
+            if !unmodifiable.is_empty() {
+                warn!(
+                    "{} options of {} can't be migrated in place: {}",
+                    kind_name(source.kind),
+                    source.name,
+                    unmodifiable.join(", ")
+                );
+            }
+
+            SchemaChangeEntry {
+                options: options.clone(),
+                ..entry(source, Change::Changed)
+            }
+        }
+        SchemaChange::Index(change) => SchemaChangeEntry {
+            kind: "index",
+            name: format!("{}.{}", change.collection, change.name),
+            change: change.kind.change(),
+            options: vec![],
+        },
+    }
+}
+
+// either connection string or name of environment profile, overrides of command line apply to
+// profiles too
+async fn connect_to(config: &Config, overrides: &Overrides, target: &str) -> Result<Database> {
+    if target.contains("://") {
+        return connect_uri(target, &config.db.name)
+            .await
+            .context(ErrorKind::Connection);
+    }
+
+    let config =
+        Config::load(Some(&config.path), Some(target), overrides).context(ErrorKind::Config)?;
+
+    connect(&config).await
+}
+
+impl MigrationController {
+    // up transforms target into source, views go last because they depend on collections
+    pub async fn diff_generate(&self, name: &str, diff: &SchemaDiff) -> Result<()> {
+        let changes = diff.changes();
+        let is_view = |change: &SchemaChange| match change {
+            SchemaChange::Missing(c) | SchemaChange::Extra(c) => c.kind == CollectionKind::View,
+            SchemaChange::Changed { source, .. } => source.kind == CollectionKind::View,
+            SchemaChange::Index(_) => false,
+        };
+        let ordered = changes
+            .iter()
+            .filter(|change| !is_view(change))
+            .chain(changes.iter().filter(|change| is_view(change)))
+            .collect::<Vec<_>>();
+
+        let up = ordered
+            .iter()
+            .flat_map(|change| change.up_commands())
+            .collect::<Vec<_>>();
+        let down = ordered
+            .iter()
+            .rev()
+            .flat_map(|change| change.down_commands())
+            .collect::<Vec<_>>();
+        let description = format!("apply {} schema differences", changes.len());
+
+        self.create_generated(
+            name,
+            MigrationContent {
+                description: description.clone(),
+                commands: up,
+            },
+            MigrationContent {
+                description: format!("revert: {description}"),
+                commands: down,
+            },
+        )
+        .await
+    }
+}
+
+fn kind_name(kind: CollectionKind) -> &'static str {
+    match kind {
+        CollectionKind::Collection => "collection",
+        CollectionKind::View => "view",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indexes::IndexChangeKind;
+
+    fn collection(name: &str, options: Document, indexes: Vec<Document>) -> CollectionSchema {
+        CollectionSchema {
+            name: name.to_string(),
+            kind: CollectionKind::Collection,
+            options,
+            indexes,
+        }
+    }
+
+    fn view(name: &str, pipeline: Vec<Document>) -> CollectionSchema {
+        CollectionSchema {
+            name: name.to_string(),
+            kind: CollectionKind::View,
+            options: doc! { "viewOn": "users", "pipeline": pipeline },
+            indexes: vec![],
+        }
+    }
+
+    fn snapshot(collections: Vec<CollectionSchema>) -> SchemaSnapshot {
+        SchemaSnapshot { collections }
+    }
+
+    #[test]
+    fn same_schemas_have_no_changes() {
+        let index = doc! { "key": { "email": 1 }, "name": "email_1" };
+        let source = snapshot(vec![collection("users", doc! {}, vec![index.clone()])]);
+        let target = snapshot(vec![collection("users", doc! {}, vec![index])]);
+
+        assert!(diff_schemas(&source, &target).is_empty());
+    }
+
+    #[test]
+    fn missing_and_extra_collections_are_created_and_dropped() {
+        let source = snapshot(vec![collection("users", doc! { "capped": true }, vec![])]);
+        let target = snapshot(vec![collection("logs", doc! {}, vec![])]);
+        let changes = diff_schemas(&source, &target);
+
+        assert!(matches!(
+            changes[..],
+            [SchemaChange::Missing(CollectionSchema { name: missing, .. }),
+             SchemaChange::Extra(CollectionSchema { name: extra, .. })]
+                if missing == "users" && extra == "logs"
+        ));
+        assert_eq!(
+            changes[0].up_commands(),
+            vec![doc! { "create": "users", "capped": true }]
+        );
+        assert_eq!(changes[0].down_commands(), vec![doc! { "drop": "users" }]);
+        assert_eq!(changes[1].up_commands(), vec![doc! { "drop": "logs" }]);
+    }
+
+    #[test]
+    fn changed_validator_is_modified_in_place() {
+        let validator = doc! { "$jsonSchema": { "required": ["email"] } };
+        let source = snapshot(vec![collection(
+            "users",
+            doc! { "validator": validator.clone() },
+            vec![],
+        )]);
+        let target = snapshot(vec![collection("users", doc! {}, vec![])]);
+        let changes = diff_schemas(&source, &target);
+
+        assert!(matches!(
+            &changes[..],
+            [SchemaChange::Changed { options, .. }] if options == &["validator"]
+        ));
+        assert_eq!(
+            changes[0].up_commands(),
+            vec![doc! { "collMod": "users", "validator": validator }]
+        );
+        // validator which doesn't exist in target is removed on down
+        assert_eq!(
+            changes[0].down_commands(),
+            vec![doc! { "collMod": "users", "validator": {} }]
+        );
+    }
+
+    #[test]
+    fn collection_replaced_by_view_is_dropped_and_created() {
+        let source = snapshot(vec![view("active", vec![doc! { "$match": {} }])]);
+        let target = snapshot(vec![collection("active", doc! {}, vec![])]);
+        let changes = diff_schemas(&source, &target);
+
+        assert!(matches!(
+            changes[..],
+            [SchemaChange::Extra(_), SchemaChange::Missing(_)]
+        ));
+    }
+
+    #[test]
+    fn index_changes_are_included() {
+        let source = snapshot(vec![collection(
+            "users",
+            doc! {},
+            vec![doc! { "key": { "email": 1 }, "name": "email_1" }],
+        )]);
+        let target = snapshot(vec![collection("users", doc! {}, vec![])]);
+        let changes = diff_schemas(&source, &target);
+
+        assert!(matches!(
+            &changes[..],
+            [SchemaChange::Index(IndexChange { kind: IndexChangeKind::Missing, name, .. })]
+                if name == "email_1"
+        ));
+    }
+
+    #[test]
+    fn report_lists_changes_with_stable_names() {
+        let validator = doc! { "$jsonSchema": { "required": ["email"] } };
+        let diff = SchemaDiff {
+            source: snapshot(vec![collection(
+                "users",
+                doc! { "validator": validator },
+                vec![doc! { "key": { "email": 1 }, "name": "email_1" }],
+            )]),
+            target: snapshot(vec![
+                collection("users", doc! {}, vec![]),
+                view("active", vec![]),
+            ]),
+        };
+
+        assert_eq!(
+            serde_json::to_value(diff.report()).unwrap(),
+            serde_json::json!({ "changes": [
+                { "kind": "collection", "name": "users", "change": "changed", "options": ["validator"] },
+                { "kind": "index", "name": "users.email_1", "change": "missing", "options": [] },
+                { "kind": "view", "name": "active", "change": "extra", "options": [] },
+            ] })
+        );
+    }
+}
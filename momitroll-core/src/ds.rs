@@ -9,6 +9,13 @@ pub struct Migration {
     pub applied_at: Option<DateTime>,
     pub status: MigrationStatus,
     pub description: Option<String>,
+    pub checksum: Option<i32>,
+    // last failure, cleared when migration is applied
+    pub failed_at: Option<DateTime>,
+    pub error: Option<String>,
+    // start of `up` run which applied migration and time it took
+    pub run_at: Option<DateTime>,
+    pub duration_ms: Option<i64>,
 }
 
 impl Migration {
@@ -20,7 +27,7 @@ impl Migration {
     }
 }
 
-#[derive(Serialize, Deserialize, Default)]
+#[derive(Serialize, Deserialize, Default, Clone, Copy)]
 pub enum MigrationStatus {
     #[serde(rename = "pending")]
     #[default]
@@ -47,11 +54,143 @@ impl fmt::Display for MigrationStatus {
     }
 }
 
+#[derive(Clone, Copy)]
+pub enum Direction {
+    Up,
+    Down,
+}
+
+impl Direction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Direction::Up => "up",
+            Direction::Down => "down",
+        }
+    }
+}
+
+pub const TODO_DESCRIPTION: &str = "TODO: describe which changes will do this migration";
+
+#[derive(Clone, Copy, Default)]
+pub enum MigrationFormat {
+    #[default]
+    Json,
+    Json5,
+    Yaml,
+    Toml,
+    Js,
+}
+
+impl MigrationFormat {
+    pub const ALL: [MigrationFormat; 5] = [
+        MigrationFormat::Json,
+        MigrationFormat::Json5,
+        MigrationFormat::Yaml,
+        MigrationFormat::Toml,
+        MigrationFormat::Js,
+    ];
+
+    // every extension accepted by `from_extension`, preferred ones first
+    pub const EXTENSIONS: [&'static str; 6] = ["json", "json5", "yaml", "yml", "toml", "js"];
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            MigrationFormat::Json => "json",
+            MigrationFormat::Json5 => "json5",
+            MigrationFormat::Yaml => "yaml",
+            MigrationFormat::Toml => "toml",
+            MigrationFormat::Js => "js",
+        }
+    }
+
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        match ext {
+            "yml" => Some(MigrationFormat::Yaml),
+            _ => Self::ALL
+                .into_iter()
+                .find(|format| format.extension() == ext),
+        }
+    }
+
+    pub fn template(&self) -> String {
+        match self {
+            MigrationFormat::Json => format!(
+                r#"{{
+    "description": "{TODO_DESCRIPTION}",
+    "commands": [
+
+    ]
+}}
+"#
+            ),
+            MigrationFormat::Json5 => format!(
+                r#"{{
+    // mongodb database commands, extended json ($oid, $date, $numberLong) is supported
+    description: "{TODO_DESCRIPTION}",
+    commands: [
+
+    ],
+}}
+"#
+            ),
+            MigrationFormat::Yaml => format!(
+                r#"description: "{TODO_DESCRIPTION}"
+# mongodb database commands, e.g. - {{ create: users }}
+commands: []
+"#
+            ),
+            MigrationFormat::Toml => format!(
+                r#"description = "{TODO_DESCRIPTION}"
+commands = [
+    # mongodb database commands, e.g. {{ create = "users" }}
+]
+"#
+            ),
+            MigrationFormat::Js => format!(
+                r#"// `db` mirrors mongosh api, e.g. db.users.updateMany({{}}, {{ $set: {{ active: true }} }})
+// commands run as the script calls them, `vars` holds config vars, e.g. vars.ttl_days
+description = "{TODO_DESCRIPTION}";
+
+"#
+            ),
+        }
+    }
+}
+
+impl std::str::FromStr for MigrationFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_extension(s).ok_or_else(|| format!("unknown migration format: {s}"))
+    }
+}
+
 pub struct MigrationContent {
     pub description: String,
     pub commands: Vec<Document>,
 }
 
+impl Serialize for MigrationContent {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use bson::Bson;
+        use serde::ser::SerializeStruct;
+
+        let commands = self
+            .commands
+            .iter()
+            .map(|command| Bson::Document(command.clone()).into_relaxed_extjson())
+            .collect::<Vec<_>>();
+
+        let mut state = serializer.serialize_struct("MigrationContent", 2)?;
+        state.serialize_field("description", &self.description)?;
+        state.serialize_field("commands", &commands)?;
+        state.end()
+    }
+}
+
 impl<'de> Deserialize<'de> for MigrationContent {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -78,14 +217,15 @@ impl<'de> Deserialize<'de> for MigrationContent {
 
         let mut commands = vec![];
 
-        for command_obj in command_objects {
+        for (i, command_obj) in command_objects.iter().enumerate() {
+            // objects are decoded as extended json v2, both canonical and relaxed forms
             let command = Bson::try_from(
                 command_obj
                     .as_object()
                     .ok_or_else(|| Error::custom("each command must be an object"))?
                     .clone(),
             )
-            .map_err(|_| Error::custom("failed to convert command to Bson"))?;
+            .map_err(|e| Error::custom(format!("command #{i} is not valid extended json: {e}")))?;
 
             match command.as_document() {
                 Some(doc) => commands.push(doc.clone()),
@@ -99,3 +239,9 @@ impl<'de> Deserialize<'de> for MigrationContent {
         })
     }
 }
+
+#[derive(Serialize, Deserialize)]
+pub struct SquashManifest {
+    pub replaces: Vec<String>,
+    pub squashed_at: String,
+}
@@ -0,0 +1,429 @@
+use anyhow::Result;
+use bson::{Bson, Document, doc};
+use futures::stream::TryStreamExt;
+use std::{fmt::Write, path::Path};
+use tracing::{info, warn};
+
+use crate::{
+    data::{DataReader, LoadData},
+    ds::{Direction, Migration, MigrationStatus},
+    loader::{checksum, load_migration, migration_file},
+    migration::MigrationController,
+    template::Variables,
+};
+use momitroll_config::Config;
+use momitroll_util::common::get_app_version;
+
+#[derive(Clone, Copy)]
+pub enum ExportFormat {
+    Mongosh,
+}
+
+impl std::str::FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mongosh" => Ok(ExportFormat::Mongosh),
+            _ => Err(format!("unknown export format: {s}")),
+        }
+    }
+}
+
+pub struct ExportOptions {
+    pub format: ExportFormat,
+    // inclusive range of migration names, pending or last applied ones when missing
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub down: bool,
+}
+
+// every command must succeed and every changelog update must match its document
+const MONGOSH_HELPERS: &str = r#"function check(res) {
+  if (res.ok !== 1 || res.writeErrors || res.writeConcernError) {
+    throw new Error("command failed: " + EJSON.stringify(res));
+  }
+  return res;
+}
+
+function record(res, name) {
+  if (check(res).n !== 1) {
+    throw new Error("migration " + name + " is not in changelog");
+  }
+}
+"#;
+
+impl MigrationController {
+    // export only reads database, script is applied by hand, e.g. `mongosh <uri> export.js`
+    pub async fn export(&self, options: &ExportOptions) -> Result<String> {
+        self.check_migration_collection().await?;
+
+        let direction = match options.down {
+            true => Direction::Down,
+            false => Direction::Up,
+        };
+        let recorded: Vec<Migration> = self
+            .db
+            .collection::<Migration>(&self.config.migration.coll_name())
+            .find(doc! {})
+            .sort(doc! { "name": 1 })
+            .await?
+            .try_collect()
+            .await?;
+        let migrations = export_range(recorded, options, direction);
+
+        if migrations.is_empty() {
+            warn!("no migrations to export");
+        }
+
+        let script = match options.format {
+            ExportFormat::Mongosh => mongosh_script(&self.config, &migrations, direction)?,
+        };
+
+        info!(
+            "exported {} migrations ({})",
+            migrations.len(),
+            direction.as_str()
+        );
+
+        Ok(script)
+    }
+}
+
+// changelog records sorted by name
+fn export_range(
+    mut migrations: Vec<Migration>,
+    options: &ExportOptions,
+    direction: Direction,
+) -> Vec<Migration> {
+    let ranged = options.from.is_some() || options.to.is_some();
+    migrations.retain(|m| {
+        options
+            .from
+            .as_deref()
+            .is_none_or(|from| m.name.as_str() >= from)
+            && options.to.as_deref().is_none_or(|to| m.name.as_str() <= to)
+    });
+
+    match direction {
+        // range is exported as is, so it can be replayed on another database
+        Direction::Up if ranged => {}
+        Direction::Up => migrations.retain(|m| matches!(m.status, MigrationStatus::Pending)),
+        Direction::Down if ranged => {
+            migrations.retain(|m| matches!(m.status, MigrationStatus::Applied));
+            migrations.reverse();
+        }
+        // like `down`, only the last applied migration without range
+        Direction::Down => {
+            migrations.retain(|m| matches!(m.status, MigrationStatus::Applied));
+            migrations.sort_by_key(|m| std::cmp::Reverse(m.applied_at));
+            migrations.truncate(1);
+        }
+    }
+
+    migrations
+}
+
+fn mongosh_script(
+    config: &Config,
+    migrations: &[Migration],
+    direction: Direction,
+) -> Result<String> {
+    use chrono::{SecondsFormat, Utc};
+
+    let mut out = String::new();
+
+    writeln!(
+        out,
+        "// generated by momitroll v{} at {}",
+        get_app_version(),
+        Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true)
+    )?;
+    writeln!(
+        out,
+        "// database: {}, environment: {}, direction: {}, migrations: {}",
+        config.db.name,
+        config.env.as_deref().unwrap_or("<default>"),
+        direction.as_str(),
+        migrations.len()
+    )?;
+    writeln!(
+        out,
+        "// hooks and snapshot are not part of script, run it with `mongosh <uri> <file>`\n"
+    )?;
+    writeln!(out, "db = db.getSiblingDB({});", js_string(&config.db.name))?;
+    writeln!(out, "const runAt = new Date();\n")?;
+    writeln!(out, "{MONGOSH_HELPERS}")?;
+
+    let lock_coll = js_string(&config.migration.lock_coll_name());
+    writeln!(
+        out,
+        "check(db.runCommand({{ insert: {lock_coll}, documents: [{{ _id: \"migration\", locked_at: runAt, pid: 0 }}] }}));\n"
+    )?;
+    writeln!(out, "try {{")?;
+
+    for migration in migrations {
+        mongosh_migration(&mut out, config, migration, direction)?;
+    }
+
+    writeln!(out, "}} finally {{")?;
+    writeln!(
+        out,
+        "  check(db.runCommand({{ delete: {lock_coll}, deletes: [{{ q: {{ _id: \"migration\" }}, limit: 1 }}] }}));"
+    )?;
+    writeln!(out, "}}")?;
+
+    Ok(out)
+}
+
+fn mongosh_migration(
+    out: &mut String,
+    config: &Config,
+    migration: &Migration,
+    direction: Direction,
+) -> Result<()> {
+    let name = &migration.name;
+    let file_path = migration_file(&config.migration.dir, name, direction)?;
+    let base_dir = file_path.parent().unwrap_or(Path::new("."));
+    let content = load_migration(&file_path, &Variables::new(config, Some(name)))?;
+
+    writeln!(
+        out,
+        "  // {} {name}: {}",
+        direction.as_str(),
+        content.description.replace('\n', " ")
+    )?;
+
+    for command in &content.commands {
+        match LoadData::from_command(command, base_dir)? {
+            Some(load) => mongosh_load_data(out, config, &load)?,
+            None => writeln!(out, "  check(db.runCommand({}));", ejson(command, true)?)?,
+        }
+    }
+
+    let update = match direction {
+        Direction::Up => format!(
+            "{{ $set: {{ applied_at: new Date(), status: \"applied\", description: {}, checksum: NumberInt({}), run_at: runAt }}, $unset: {{ failed_at: \"\", error: \"\" }} }}",
+            js_string(&content.description),
+            checksum(&file_path, &content)?
+        ),
+        Direction::Down => "{ $set: { applied_at: null, status: \"pending\" }, $unset: { run_at: \"\", duration_ms: \"\", failed_at: \"\", error: \"\" } }".to_string(),
+    };
+
+    writeln!(
+        out,
+        "  record(db.runCommand({{ update: {}, updates: [{{ q: {{ name: {} }}, u: {update} }}] }}), {});\n",
+        js_string(&config.migration.coll_name()),
+        js_string(name),
+        js_string(name)
+    )?;
+
+    Ok(())
+}
+
+// data files are inlined as batches of `insert`, so script doesn't depend on them
+fn mongosh_load_data(out: &mut String, config: &Config, load: &LoadData) -> Result<()> {
+    let batch_size = load.batch_size.unwrap_or(config.migration.data_batch_size);
+    let mut batch = Vec::with_capacity(batch_size);
+    let mut reader = DataReader::open(&load.file)?.peekable();
+
+    while let Some(doc) = reader.next() {
+        batch.push(Bson::Document(doc?));
+
+        if batch.len() >= batch_size || reader.peek().is_none() {
+            let insert = doc! {
+                "insert": &load.collection,
+                "documents": std::mem::take(&mut batch),
+            };
+            writeln!(out, "  check(db.runCommand({}));", ejson(&insert, false)?)?;
+        }
+    }
+
+    Ok(())
+}
+
+// canonical extended json keeps types, e.g. `{"$numberLong": "1"}`, shell restores them
+fn ejson(command: &Document, pretty: bool) -> Result<String> {
+    let value = Bson::Document(command.clone()).into_canonical_extjson();
+    let text = match pretty {
+        true => serde_json::to_string_pretty(&value)?.replace('\n', "\n  "),
+        false => serde_json::to_string(&value)?,
+    };
+
+    Ok(format!("EJSON.deserialize({text})"))
+}
+
+fn js_string(value: &str) -> String {
+    serde_json::Value::String(value.to_string()).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::{env, fs, path::PathBuf};
+
+    const USERS_UP: &str = "20240101000000_users";
+    const ADMINS_UP: &str = "20240102000000_admins";
+
+    // two migrations, the second one loads data file in batches of two documents
+    fn config(name: &str) -> Config {
+        let dir = env::temp_dir().join(format!("momitroll-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let write = |migration: &str, file: &str, content: &str| {
+            let path: PathBuf = dir.join(migration).join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        };
+
+        write(
+            USERS_UP,
+            &format!("{USERS_UP}_up.json"),
+            r#"{
+                "description": "create users",
+                "commands": [
+                    { "create": "users" },
+                    { "createIndexes": "users", "indexes": [{ "key": { "email": 1 }, "name": "email_1", "unique": true }] }
+                ]
+            }"#,
+        );
+        write(
+            USERS_UP,
+            &format!("{USERS_UP}_down.json"),
+            r#"{ "description": "drop users", "commands": [{ "drop": "users" }] }"#,
+        );
+        write(
+            ADMINS_UP,
+            &format!("{ADMINS_UP}_up.json"),
+            r#"{
+                "description": "add admins",
+                "commands": [{ "$loadData": { "collection": "users", "file": "admins.json", "batchSize": 2 } }]
+            }"#,
+        );
+        write(
+            ADMINS_UP,
+            "admins.json",
+            r#"[
+                { "_id": 1, "email": "root@example.com" },
+                { "_id": 2, "email": "ops@example.com" },
+                { "_id": 3, "email": "dev@example.com", "since": { "$date": "2024-01-02T00:00:00Z" } }
+            ]"#,
+        );
+        write(
+            ADMINS_UP,
+            &format!("{ADMINS_UP}_down.json"),
+            r#"{
+                "description": "remove admins",
+                "commands": [{ "delete": "users", "deletes": [{ "q": { "_id": { "$in": [1, 2, 3] } }, "limit": 0 }] }]
+            }"#,
+        );
+
+        serde_json::from_value(json!({
+            "migration": { "dir": dir.display().to_string(), "changelog-coll-name": "migrations" },
+            "db": { "name": "app" },
+        }))
+        .unwrap()
+    }
+
+    fn recorded(users: MigrationStatus, admins: MigrationStatus) -> Vec<Migration> {
+        [(USERS_UP, users, 1), (ADMINS_UP, admins, 2)]
+            .into_iter()
+            .map(|(name, status, day)| Migration {
+                status,
+                applied_at: matches!(status, MigrationStatus::Applied)
+                    .then(|| bson::DateTime::from_millis(day * 86_400_000)),
+                ..Migration::new(name.to_string())
+            })
+            .collect()
+    }
+
+    fn options(from: Option<&str>, to: Option<&str>, down: bool) -> ExportOptions {
+        ExportOptions {
+            format: ExportFormat::Mongosh,
+            from: from.map(str::to_string),
+            to: to.map(str::to_string),
+            down,
+        }
+    }
+
+    fn names(migrations: &[Migration]) -> Vec<&str> {
+        migrations.iter().map(|m| m.name.as_str()).collect()
+    }
+
+    // first line holds version and time of generation
+    fn script(config: &Config, options: &ExportOptions, recorded: Vec<Migration>) -> String {
+        let direction = match options.down {
+            true => Direction::Down,
+            false => Direction::Up,
+        };
+        let migrations = export_range(recorded, options, direction);
+        let script = mongosh_script(config, &migrations, direction).unwrap();
+
+        script.split_once('\n').unwrap().1.to_string()
+    }
+
+    #[test]
+    fn up_range_matches_golden_script() {
+        let config = config("export-up");
+        let script = script(
+            &config,
+            &options(Some(USERS_UP), Some(ADMINS_UP), false),
+            recorded(MigrationStatus::Applied, MigrationStatus::Pending),
+        );
+
+        assert_eq!(script, include_str!("../testdata/export/up.js"));
+    }
+
+    #[test]
+    fn down_range_matches_golden_script() {
+        let config = config("export-down");
+        let script = script(
+            &config,
+            &options(Some(USERS_UP), Some(ADMINS_UP), true),
+            recorded(MigrationStatus::Applied, MigrationStatus::Applied),
+        );
+
+        assert_eq!(script, include_str!("../testdata/export/down.js"));
+    }
+
+    #[test]
+    fn range_selects_migrations() {
+        let select = |recorded, from, to, down| {
+            let direction = match down {
+                true => Direction::Down,
+                false => Direction::Up,
+            };
+            let migrations = export_range(recorded, &options(from, to, down), direction);
+            names(&migrations).join(",")
+        };
+        let (applied, pending) = (MigrationStatus::Applied, MigrationStatus::Pending);
+
+        // pending ones without range, whole range otherwise
+        assert_eq!(
+            select(recorded(applied, pending), None, None, false),
+            ADMINS_UP
+        );
+        assert_eq!(
+            select(recorded(applied, pending), Some(USERS_UP), None, false),
+            format!("{USERS_UP},{ADMINS_UP}")
+        );
+        assert_eq!(
+            select(recorded(applied, pending), None, Some(USERS_UP), false),
+            USERS_UP
+        );
+        // last applied one without range, applied ones of range in reverse otherwise
+        assert_eq!(
+            select(recorded(applied, applied), None, None, true),
+            ADMINS_UP
+        );
+        assert_eq!(
+            select(recorded(applied, applied), None, Some(ADMINS_UP), true),
+            format!("{ADMINS_UP},{USERS_UP}")
+        );
+        assert_eq!(
+            select(recorded(applied, pending), Some(USERS_UP), None, true),
+            USERS_UP
+        );
+    }
+}
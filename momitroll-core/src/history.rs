@@ -0,0 +1,212 @@
+use anyhow::{Context, Result};
+use bson::{DateTime, doc};
+use futures::stream::TryStreamExt;
+use glob::Pattern;
+use mongodb::{
+    IndexModel,
+    error::{CommandError, ErrorKind as DbErrorKind},
+    options::IndexOptions,
+};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tracing::{debug, warn};
+
+use crate::{
+    ds::Direction,
+    migration::MigrationController,
+    report::{HistoryEntry, HistoryReport},
+    status::{format_time, parse_since},
+};
+use momitroll_util::common::get_app_version;
+
+const TTL_INDEX: &str = "ttl";
+const INDEX_OPTIONS_CONFLICT_CODES: [i32; 2] = [85, 86];
+
+// single execution of migration, documents are only inserted
+#[derive(Serialize, Deserialize)]
+struct HistoryEvent {
+    migration: String,
+    direction: String,
+    started_at: DateTime,
+    ended_at: DateTime,
+    duration_ms: i64,
+    // `success` or `failure`
+    outcome: String,
+    error: Option<String>,
+    // of up file in both directions, missing when migration failed
+    checksum: Option<i32>,
+    environment: Option<String>,
+    host: Option<String>,
+    user: Option<String>,
+    version: String,
+}
+
+pub struct HistoryFilter {
+    // glob of migration names
+    pub name: Option<String>,
+    pub direction: Option<String>,
+    pub failed: bool,
+    // rfc 3339 time or date in configured timezone
+    pub since: Option<String>,
+    pub limit: usize,
+}
+
+impl MigrationController {
+    // history is an audit trail, failing to write it doesn't fail migration
+    pub(crate) async fn record_history(
+        &self,
+        migration: &str,
+        direction: Direction,
+        started_at: DateTime,
+        duration_ms: i64,
+        // checksum of up file when it's known
+        result: Result<Option<i32>, &anyhow::Error>,
+    ) {
+        let event = HistoryEvent {
+            migration: migration.to_string(),
+            direction: direction.as_str().to_string(),
+            started_at,
+            ended_at: DateTime::now(),
+            duration_ms,
+            outcome: match result {
+                Ok(_) => "success",
+                Err(_) => "failure",
+            }
+            .to_string(),
+            error: result.as_ref().err().map(|e| format!("{e:#}")),
+            checksum: result.ok().flatten(),
+            environment: self.config.env.clone(),
+            host: whoami::fallible::hostname().ok(),
+            user: whoami::fallible::username().ok(),
+            version: get_app_version().to_string(),
+        };
+
+        let res = self
+            .db
+            .collection::<HistoryEvent>(&self.config.migration.history_coll_name())
+            .insert_one(event)
+            .await;
+
+        if let Err(e) = res {
+            warn!("failed to record history of migration {migration}: {e}");
+        }
+    }
+
+    // ttl index follows config, it's dropped when retention is removed
+    pub(crate) async fn sync_history_ttl(&self) {
+        let collection = self
+            .db
+            .collection::<HistoryEvent>(&self.config.migration.history_coll_name());
+
+        let res = match self.config.migration.history_ttl_days {
+            Some(days) => {
+                let expire_after = Duration::from_secs(u64::from(days) * 24 * 60 * 60);
+                let index = IndexModel::builder()
+                    .keys(doc! { "ended_at": 1 })
+                    .options(
+                        IndexOptions::builder()
+                            .name(TTL_INDEX.to_string())
+                            .expire_after(expire_after)
+                            .build(),
+                    )
+                    .build();
+
+                match collection.create_index(index).await {
+                    Err(e) if is_options_conflict(&e) => self
+                        .db
+                        .run_command(doc! {
+                            "collMod": self.config.migration.history_coll_name(),
+                            "index": {
+                                "name": TTL_INDEX,
+                                "expireAfterSeconds": expire_after.as_secs() as i64,
+                            },
+                        })
+                        .await
+                        .map(|_| ()),
+                    res => res.map(|_| ()),
+                }
+            }
+            None => {
+                if let Err(e) = collection.drop_index(TTL_INDEX).await {
+                    debug!("no ttl index of history to drop: {e}");
+                }
+                Ok(())
+            }
+        };
+
+        if let Err(e) = res {
+            warn!("failed to update ttl of history: {e}");
+        }
+    }
+
+    pub async fn history(&self, filter: &HistoryFilter) -> Result<HistoryReport> {
+        let tz = self.config.timezone()?;
+        let pattern = filter
+            .name
+            .as_deref()
+            .map(Pattern::new)
+            .transpose()
+            .context("invalid glob of migration name")?;
+
+        let mut query = doc! {};
+        if let Some(direction) = &filter.direction {
+            query.insert("direction", direction);
+        }
+        if filter.failed {
+            query.insert("outcome", "failure");
+        }
+        if let Some(since) = &filter.since {
+            query.insert(
+                "started_at",
+                doc! { "$gte": DateTime::from_chrono(parse_since(since, &tz)?) },
+            );
+        }
+
+        let mut cursor = self
+            .db
+            .collection::<HistoryEvent>(&self.config.migration.history_coll_name())
+            .find(query)
+            .sort(doc! { "started_at": -1 })
+            .await?;
+        let mut events = vec![];
+
+        // glob can't be a query, so limit is applied while reading
+        while events.len() < filter.limit
+            && let Some(event) = cursor.try_next().await?
+        {
+            if pattern
+                .as_ref()
+                .is_some_and(|pattern| !pattern.matches(&event.migration))
+            {
+                continue;
+            }
+
+            events.push(HistoryEntry {
+                migration: event.migration,
+                direction: event.direction,
+                started_at: format_time(event.started_at, &tz),
+                ended_at: format_time(event.ended_at, &tz),
+                duration_ms: event.duration_ms,
+                outcome: event.outcome,
+                error: event.error,
+                checksum: event.checksum,
+                environment: event.environment,
+                host: event.host,
+                user: event.user,
+                version: event.version,
+            });
+        }
+
+        Ok(HistoryReport {
+            timezone: tz.name().to_string(),
+            events,
+        })
+    }
+}
+
+fn is_options_conflict(error: &mongodb::error::Error) -> bool {
+    matches!(
+        error.kind.as_ref(),
+        DbErrorKind::Command(CommandError { code, .. }) if INDEX_OPTIONS_CONFLICT_CODES.contains(code)
+    )
+}
@@ -0,0 +1,162 @@
+use anyhow::{Result, anyhow};
+use tracing::{info, warn};
+
+use crate::{ds::Direction, migration::MigrationController};
+use momitroll_config::Hook;
+use momitroll_util::process::shell_command;
+
+#[derive(Clone, Copy)]
+pub enum HookPoint {
+    BeforeUp,
+    AfterUp,
+    BeforeEach,
+    AfterEach,
+    OnFailure,
+}
+
+impl HookPoint {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HookPoint::BeforeUp => "before-up",
+            HookPoint::AfterUp => "after-up",
+            HookPoint::BeforeEach => "before-each",
+            HookPoint::AfterEach => "after-each",
+            HookPoint::OnFailure => "on-failure",
+        }
+    }
+}
+
+pub struct HookContext {
+    pub direction: Direction,
+    pub migration: Option<String>,
+    pub description: Option<String>,
+}
+
+impl HookContext {
+    pub fn new(direction: Direction) -> Self {
+        Self {
+            direction,
+            migration: None,
+            description: None,
+        }
+    }
+}
+
+impl MigrationController {
+    pub(crate) async fn run_hooks(&self, point: HookPoint, ctx: &HookContext) -> Result<()> {
+        for hook in self.hooks(point) {
+            if !Self::is_hook_matched(hook, ctx) {
+                continue;
+            }
+
+            if let Err(e) = self.run_hook(hook, point, ctx, None).await {
+                if hook.abort_on_failure {
+                    return Err(e.context(format!("{} hook failed", point.as_str())));
+                }
+
+                warn!("{} hook failed: {e}", point.as_str());
+            }
+        }
+
+        Ok(())
+    }
+
+    // failure hooks never abort, the run is already failed
+    pub(crate) async fn run_failure_hooks(&self, ctx: &HookContext, error: &anyhow::Error) {
+        let point = HookPoint::OnFailure;
+
+        for hook in self.hooks(point) {
+            if !Self::is_hook_matched(hook, ctx) {
+                continue;
+            }
+
+            if let Err(e) = self.run_hook(hook, point, ctx, Some(error)).await {
+                warn!("{} hook failed: {e}", point.as_str());
+            }
+        }
+    }
+
+    fn hooks(&self, point: HookPoint) -> &[Hook] {
+        let hooks = &self.config.hooks;
+
+        match point {
+            HookPoint::BeforeUp => &hooks.before_up,
+            HookPoint::AfterUp => &hooks.after_up,
+            HookPoint::BeforeEach => &hooks.before_each,
+            HookPoint::AfterEach => &hooks.after_each,
+            HookPoint::OnFailure => &hooks.on_failure,
+        }
+    }
+
+    fn is_hook_matched(hook: &Hook, ctx: &HookContext) -> bool {
+        match &ctx.migration {
+            Some(migration) if !hook.only.is_empty() => hook.only.iter().any(|only| {
+                // migrations can be referenced with or without timestamp prefix
+                only == migration
+                    || migration
+                        .split_once('_')
+                        .is_some_and(|(_, name)| only == name)
+            }),
+            _ => true,
+        }
+    }
+
+    async fn run_hook(
+        &self,
+        hook: &Hook,
+        point: HookPoint,
+        ctx: &HookContext,
+        error: Option<&anyhow::Error>,
+    ) -> Result<()> {
+        match (&hook.command, &hook.migration) {
+            (Some(command), None) => self.run_hook_command(command, point, ctx, error),
+            (None, Some(path)) => {
+                info!("running {} hook migration: {path}", point.as_str());
+                self.apply_commands(path, None).await.map(|_| ())
+            }
+            _ => Err(anyhow!(
+                "{} hook must have exactly one of `command` or `migration`",
+                point.as_str()
+            )),
+        }
+    }
+
+    fn run_hook_command(
+        &self,
+        command: &str,
+        point: HookPoint,
+        ctx: &HookContext,
+        error: Option<&anyhow::Error>,
+    ) -> Result<()> {
+        info!("running {} hook: {command}", point.as_str());
+
+        let mut process = shell_command(command);
+
+        process
+            .current_dir(self.config.dir())
+            .env("MOMITROLL_HOOK", point.as_str())
+            .env("MOMITROLL_DIRECTION", ctx.direction.as_str())
+            .env("MOMITROLL_DB_NAME", &self.config.db.name)
+            .env("MOMITROLL_MIGRATION_DIR", &self.config.migration.dir)
+            .env(
+                "MOMITROLL_MIGRATION",
+                ctx.migration.as_deref().unwrap_or_default(),
+            )
+            .env(
+                "MOMITROLL_MIGRATION_DESCRIPTION",
+                ctx.description.as_deref().unwrap_or_default(),
+            );
+
+        if let Some(error) = error {
+            process.env("MOMITROLL_ERROR", format!("{error:#}"));
+        }
+
+        let status = process.status()?;
+
+        if !status.success() {
+            return Err(anyhow!("command `{command}` exited with {status}"));
+        }
+
+        Ok(())
+    }
+}
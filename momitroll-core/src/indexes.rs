@@ -0,0 +1,376 @@
+use anyhow::{Result, anyhow};
+use bson::{Bson, Document, doc};
+use std::path::Path;
+use tracing::{info, warn};
+
+use crate::{
+    ds::MigrationContent,
+    loader::load_file,
+    migration::MigrationController,
+    report::{Change, IndexEntry, IndexesReport},
+    schema::{SchemaSnapshot, is_equivalent},
+};
+
+// added by server on its own, never part of desired definition
+const SERVER_INDEX_FIELDS: [&str; 3] = ["background", "textIndexVersion", "2dsphereIndexVersion"];
+
+pub enum IndexChangeKind {
+    Missing,
+    Extra,
+    Changed,
+}
+
+impl IndexChangeKind {
+    pub fn change(&self) -> Change {
+        match self {
+            IndexChangeKind::Missing => Change::Missing,
+            IndexChangeKind::Extra => Change::Extra,
+            IndexChangeKind::Changed => Change::Changed,
+        }
+    }
+}
+
+pub struct IndexChange {
+    pub collection: String,
+    pub name: String,
+    pub kind: IndexChangeKind,
+    pub current: Option<Document>,
+    pub desired: Option<Document>,
+}
+
+impl IndexChange {
+    pub fn up_commands(&self) -> Vec<Document> {
+        Self::commands(
+            &self.collection,
+            &self.name,
+            self.desired.as_ref(),
+            &self.kind,
+        )
+    }
+
+    pub fn down_commands(&self) -> Vec<Document> {
+        let kind = match self.kind {
+            IndexChangeKind::Missing => IndexChangeKind::Extra,
+            IndexChangeKind::Extra => IndexChangeKind::Missing,
+            IndexChangeKind::Changed => IndexChangeKind::Changed,
+        };
+
+        Self::commands(&self.collection, &self.name, self.current.as_ref(), &kind)
+    }
+
+    fn commands(
+        collection: &str,
+        name: &str,
+        target: Option<&Document>,
+        kind: &IndexChangeKind,
+    ) -> Vec<Document> {
+        let drop = doc! { "dropIndexes": collection, "index": name };
+        let create = |index: &Document| {
+            doc! { "createIndexes": collection, "indexes": [index.clone()] }
+        };
+
+        match (kind, target) {
+            (IndexChangeKind::Missing, Some(index)) => vec![create(index)],
+            (IndexChangeKind::Extra, _) => vec![drop],
+            (IndexChangeKind::Changed, Some(index)) => vec![drop, create(index)],
+            _ => vec![],
+        }
+    }
+}
+
+// default index name of server, e.g. `email_1_created_at_-1`
+pub fn index_name(key: &Document) -> String {
+    key.iter()
+        .map(|(field, value)| match value {
+            Bson::String(s) => format!("{field}_{s}"),
+            Bson::Int32(v) => format!("{field}_{v}"),
+            Bson::Int64(v) => format!("{field}_{v}"),
+            Bson::Double(v) if v.fract() == 0.0 => format!("{field}_{}", *v as i64),
+            other => format!("{field}_{other}"),
+        })
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+// field order of index key is significant, unlike the rest of index options
+fn is_same_index(current: &Document, desired: &Document) -> bool {
+    let (Ok(current_key), Ok(desired_key)) =
+        (current.get_document("key"), desired.get_document("key"))
+    else {
+        return false;
+    };
+
+    let same_key = current_key.len() == desired_key.len()
+        && current_key
+            .iter()
+            .zip(desired_key)
+            .all(|((a_field, a), (b_field, b))| a_field == b_field && is_equivalent(a, b));
+
+    let options = |index: &Document| {
+        let mut options = index.clone();
+        options.remove("key");
+        for field in SERVER_INDEX_FIELDS {
+            options.remove(field);
+        }
+        Bson::Document(options)
+    };
+
+    same_key && is_equivalent(&options(current), &options(desired))
+}
+
+impl MigrationController {
+    pub async fn indexes_plan(&self) -> Result<IndexesReport> {
+        let changes = self.index_changes().await?;
+
+        if changes.is_empty() {
+            info!("indexes are up to date");
+        }
+
+        Ok(IndexesReport {
+            changes: changes
+                .into_iter()
+                .map(|change| IndexEntry {
+                    index: change
+                        .desired
+                        .or(change.current)
+                        .map(|index| Bson::Document(index).into_relaxed_extjson()),
+                    change: change.kind.change(),
+                    collection: change.collection,
+                    name: change.name,
+                })
+                .collect(),
+        })
+    }
+
+    pub async fn indexes_generate(&self, name: &str) -> Result<()> {
+        let changes = self.index_changes().await?;
+
+        if changes.is_empty() {
+            warn!("indexes are up to date, nothing to generate");
+            return Ok(());
+        }
+
+        let up = changes
+            .iter()
+            .flat_map(|change| change.up_commands())
+            .collect::<Vec<_>>();
+        let down = changes
+            .iter()
+            .rev()
+            .flat_map(|change| change.down_commands())
+            .collect::<Vec<_>>();
+        let description = format!("update {} indexes", changes.len());
+
+        self.create_generated(
+            name,
+            MigrationContent {
+                description: description.clone(),
+                commands: up,
+            },
+            MigrationContent {
+                description: format!("revert: {description}"),
+                commands: down,
+            },
+        )
+        .await
+    }
+
+    async fn index_changes(&self) -> Result<Vec<IndexChange>> {
+        let desired = self.load_indexes()?;
+        let snapshot = SchemaSnapshot::capture(&self.db, &self.internal_collections()).await?;
+        let mut changes = vec![];
+
+        for (collection, desired) in desired {
+            let current = snapshot
+                .collections
+                .iter()
+                .find(|c| c.name == collection)
+                .map(|c| c.indexes.clone())
+                .unwrap_or_default();
+
+            changes.extend(diff_indexes(&collection, &current, &desired));
+        }
+
+        Ok(changes)
+    }
+
+    // `{ "<collection>": [ { "key": { "email": 1 }, "unique": true } ] }`,
+    // only listed collections are managed
+    fn load_indexes(&self) -> Result<Vec<(String, Vec<Document>)>> {
+        let path = Path::new(&self.config.indexes.file);
+
+        if !path.exists() {
+            return Err(anyhow!("indexes file doesn't exist: {}", path.display()));
+        }
+
+        let value: serde_json::Value = load_file(path)?;
+        let Bson::Document(content) = Bson::try_from(value)? else {
+            return Err(anyhow!(
+                "indexes file must be an object: {}",
+                path.display()
+            ));
+        };
+
+        let mut collections = vec![];
+
+        for (collection, indexes) in content {
+            let indexes = indexes
+                .as_array()
+                .ok_or_else(|| anyhow!("indexes of {collection} must be an array"))?;
+            let mut specs = vec![];
+
+            for index in indexes {
+                let mut index = index
+                    .as_document()
+                    .ok_or_else(|| anyhow!("each index of {collection} must be an object"))?
+                    .clone();
+                let key = index
+                    .get_document("key")
+                    .map_err(|_| anyhow!("index of {collection} must have `key` object"))?
+                    .clone();
+
+                if !index.contains_key("name") {
+                    index.insert("name", index_name(&key));
+                }
+
+                specs.push(index);
+            }
+
+            collections.push((collection, specs));
+        }
+
+        Ok(collections)
+    }
+}
+
+pub fn diff_indexes(
+    collection: &str,
+    current: &[Document],
+    desired: &[Document],
+) -> Vec<IndexChange> {
+    let name = |index: &Document| index.get_str("name").unwrap_or_default().to_string();
+    let mut changes = vec![];
+
+    // drops go first, so renamed index with the same key doesn't conflict with old one,
+    // `down` runs changes in reverse and recreates dropped indexes last
+    for index in current {
+        if !desired.iter().any(|d| name(d) == name(index)) {
+            changes.push(IndexChange {
+                collection: collection.to_string(),
+                name: name(index),
+                kind: IndexChangeKind::Extra,
+                current: Some(index.clone()),
+                desired: None,
+            });
+        }
+    }
+
+    for index in desired {
+        match current.iter().find(|c| name(c) == name(index)) {
+            None => changes.push(IndexChange {
+                collection: collection.to_string(),
+                name: name(index),
+                kind: IndexChangeKind::Missing,
+                current: None,
+                desired: Some(index.clone()),
+            }),
+            Some(existing) if !is_same_index(existing, index) => changes.push(IndexChange {
+                collection: collection.to_string(),
+                name: name(index),
+                kind: IndexChangeKind::Changed,
+                current: Some(existing.clone()),
+                desired: Some(index.clone()),
+            }),
+            Some(_) => {}
+        }
+    }
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index(name: &str, key: Document) -> Document {
+        doc! { "key": key, "name": name }
+    }
+
+    fn commands(changes: &[IndexChange], down: bool) -> Vec<Document> {
+        match down {
+            false => changes.iter().flat_map(IndexChange::up_commands).collect(),
+            true => changes
+                .iter()
+                .rev()
+                .flat_map(IndexChange::down_commands)
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn index_name_matches_server_default() {
+        assert_eq!(
+            index_name(&doc! { "email": 1, "created_at": -1_i64, "bio": "text", "n": 1.0 }),
+            "email_1_created_at_-1_bio_text_n_1"
+        );
+    }
+
+    #[test]
+    fn unchanged_indexes_produce_no_changes() {
+        let current = [doc! { "key": { "email": 1 }, "name": "email_1", "unique": true }];
+        let desired = [doc! { "key": { "email": 1_i64 }, "name": "email_1", "unique": true }];
+
+        assert!(diff_indexes("users", &current, &desired).is_empty());
+    }
+
+    #[test]
+    fn changed_options_drop_and_recreate_index() {
+        let current = [index("email_1", doc! { "email": 1 })];
+        let desired = [doc! { "key": { "email": 1 }, "name": "email_1", "unique": true }];
+        let changes = diff_indexes("users", &current, &desired);
+
+        assert!(matches!(
+            changes[..],
+            [IndexChange {
+                kind: IndexChangeKind::Changed,
+                ..
+            }]
+        ));
+        assert_eq!(
+            commands(&changes, false),
+            vec![
+                doc! { "dropIndexes": "users", "index": "email_1" },
+                doc! { "createIndexes": "users", "indexes": [desired[0].clone()] },
+            ]
+        );
+        assert_eq!(
+            commands(&changes, true),
+            vec![
+                doc! { "dropIndexes": "users", "index": "email_1" },
+                doc! { "createIndexes": "users", "indexes": [current[0].clone()] },
+            ]
+        );
+    }
+
+    #[test]
+    fn renamed_index_is_dropped_before_created() {
+        let current = [index("email_1", doc! { "email": 1 })];
+        let desired = [index("users_email", doc! { "email": 1 })];
+        let changes = diff_indexes("users", &current, &desired);
+
+        assert_eq!(
+            commands(&changes, false),
+            vec![
+                doc! { "dropIndexes": "users", "index": "email_1" },
+                doc! { "createIndexes": "users", "indexes": [desired[0].clone()] },
+            ]
+        );
+        assert_eq!(
+            commands(&changes, true),
+            vec![
+                doc! { "dropIndexes": "users", "index": "users_email" },
+                doc! { "createIndexes": "users", "indexes": [current[0].clone()] },
+            ]
+        );
+    }
+}
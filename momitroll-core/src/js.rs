@@ -0,0 +1,612 @@
+use anyhow::{Result, anyhow};
+use boa_engine::{
+    Context, JsError, JsNativeError, JsResult, JsString, JsValue, NativeFunction, Source,
+};
+use bson::{Bson, Document};
+use futures::TryStreamExt;
+use mongodb::Database;
+use serde::Deserialize;
+use std::{path::PathBuf, rc::Rc};
+use tokio::runtime::Handle;
+use tracing::{debug, info};
+
+use crate::{
+    data::{LoadData, insert_data},
+    ds::MigrationContent,
+    template::Variables,
+};
+
+// every loop of script stops with error after this many iterations, so `up` can't hang
+const LOOP_ITERATION_LIMIT: u64 = 10_000_000;
+
+// mongosh-like `db` object, commands run one by one while the script runs, so reads like
+// `find` see writes made before them, without database (dry run, lint, export) writes are
+// only recorded and reads throw
+const PRELUDE: &str = r#"
+var description = undefined;
+var __commands = [];
+var __output = [];
+
+function __ejson(value) {
+    if (value instanceof Date) {
+        return { $date: value.toISOString() };
+    }
+    if (Array.isArray(value)) {
+        return value.map(__ejson);
+    }
+    if (value !== null && typeof value === "object") {
+        var res = {};
+        for (var key of Object.keys(value)) {
+            if (value[key] !== undefined) {
+                res[key] = __ejson(value[key]);
+            }
+        }
+        return res;
+    }
+    return value;
+}
+
+// dates of replies become `Date` like in mongosh, the rest stays extended json
+function __revive(key, value) {
+    if (value !== null && typeof value === "object" && !Array.isArray(value)) {
+        var keys = Object.keys(value);
+        if (keys.length === 1 && keys[0] === "$date") {
+            var date = value.$date;
+            return new Date(typeof date === "object" ? Number(date.$numberLong) : date);
+        }
+    }
+    return value;
+}
+
+function __online() {
+    return typeof __execute === "function";
+}
+
+function __offline(name) {
+    return new Error(name + " needs database, it isn't available in dry run, lint, status and export");
+}
+
+function __exec(command) {
+    if (!__online()) {
+        throw __offline(Object.keys(command)[0]);
+    }
+    return JSON.parse(__execute(JSON.stringify(__ejson(command))), __revive);
+}
+
+function __query(command) {
+    if (!__online()) {
+        throw __offline(Object.keys(command)[0]);
+    }
+    return JSON.parse(__fetch(JSON.stringify(__ejson(command))), __revive);
+}
+
+function __run(command) {
+    __commands.push(__ejson(command));
+    if (__online()) {
+        return __exec(command);
+    }
+    return new Proxy({}, {
+        get: function (target, property) {
+            if (typeof property !== "string") {
+                return undefined;
+            }
+            throw new Error("result of command is available only when migration runs");
+        },
+    });
+}
+
+function __unsupported(name, instead) {
+    return function () {
+        throw new Error(name + " is not supported, use " + instead);
+    };
+}
+
+function __indexName(keys) {
+    return Object.keys(keys).map(function (key) { return key + "_" + keys[key]; }).join("_");
+}
+
+function ObjectId(hex) {
+    if (hex === undefined) {
+        hex = Math.floor(Date.now() / 1000).toString(16).padStart(8, "0");
+        while (hex.length < 24) {
+            hex += Math.floor(Math.random() * 16).toString(16);
+        }
+    }
+    return { $oid: hex };
+}
+function ISODate(value) { return new Date(value === undefined ? Date.now() : value); }
+function NumberInt(value) { return { $numberInt: String(value) }; }
+function NumberLong(value) { return { $numberLong: String(value) }; }
+function NumberDecimal(value) { return { $numberDecimal: String(value) }; }
+function print() { __output.push(Array.prototype.join.call(arguments, " ")); }
+function printjson(value) { print(JSON.stringify(value)); }
+
+// documents are fetched on first use, modifiers only change the command
+function Cursor(command) {
+    this.__command = command;
+    this.__docs = undefined;
+    this.__index = 0;
+}
+
+for (var __modifier of ["sort", "limit", "skip", "projection", "batchSize", "hint", "collation"]) {
+    (function (field) {
+        Cursor.prototype[field] = function (value) {
+            this.__command[field] = value;
+            return this;
+        };
+    })(__modifier);
+}
+
+Cursor.prototype.__load = function () {
+    if (this.__docs === undefined) {
+        this.__docs = __query(this.__command);
+    }
+    return this.__docs;
+};
+Cursor.prototype.hasNext = function () { return this.__index < this.__load().length; };
+Cursor.prototype.next = function () {
+    if (!this.hasNext()) {
+        throw new Error("cursor is exhausted");
+    }
+    return this.__load()[this.__index++];
+};
+Cursor.prototype.toArray = function () {
+    var docs = this.__load().slice(this.__index);
+    this.__index = this.__load().length;
+    return docs;
+};
+Cursor.prototype.forEach = function (fn) { this.toArray().forEach(function (doc) { fn(doc); }); };
+Cursor.prototype.map = function (fn) { return this.toArray().map(function (doc) { return fn(doc); }); };
+Cursor.prototype.itcount = function () { return this.toArray().length; };
+
+function Collection(name) { this.name = name; }
+
+Collection.prototype.getName = function () { return this.name; };
+Collection.prototype.find = function (filter, projection) {
+    var command = { find: this.name, filter: filter === undefined ? {} : filter };
+    if (projection !== undefined) {
+        command.projection = projection;
+    }
+    return new Cursor(command);
+};
+Collection.prototype.findOne = function (filter, projection) {
+    var docs = this.find(filter, projection).limit(1).toArray();
+    return docs.length > 0 ? docs[0] : null;
+};
+Collection.prototype.countDocuments = function (filter, options) {
+    var pipeline = [{ $match: filter === undefined ? {} : filter }];
+    if (options !== undefined && options.skip !== undefined) {
+        pipeline.push({ $skip: options.skip });
+    }
+    if (options !== undefined && options.limit !== undefined) {
+        pipeline.push({ $limit: options.limit });
+    }
+    pipeline.push({ $group: { _id: 1, n: { $sum: 1 } } });
+    var res = __query({ aggregate: this.name, pipeline: pipeline, cursor: {} });
+    return res.length > 0 ? res[0].n : 0;
+};
+Collection.prototype.estimatedDocumentCount = function () {
+    return __exec({ count: this.name }).n;
+};
+Collection.prototype.distinct = function (key, filter) {
+    return __exec({ distinct: this.name, key: key, query: filter === undefined ? {} : filter }).values;
+};
+Collection.prototype.getIndexes = function () {
+    return __query({ listIndexes: this.name });
+};
+Collection.prototype.insertOne = function (doc, options) {
+    return __run(Object.assign({ insert: this.name, documents: [doc] }, options));
+};
+Collection.prototype.insertMany = function (docs, options) {
+    return __run(Object.assign({ insert: this.name, documents: docs }, options));
+};
+Collection.prototype.__update = function (filter, update, options, multi) {
+    return __run({
+        update: this.name,
+        updates: [Object.assign({ q: filter, u: update, multi: multi }, options)],
+    });
+};
+Collection.prototype.updateOne = function (filter, update, options) {
+    return this.__update(filter, update, options, false);
+};
+Collection.prototype.updateMany = function (filter, update, options) {
+    return this.__update(filter, update, options, true);
+};
+Collection.prototype.replaceOne = function (filter, replacement, options) {
+    return this.__update(filter, replacement, options, false);
+};
+Collection.prototype.deleteOne = function (filter, options) {
+    return __run({ delete: this.name, deletes: [Object.assign({ q: filter, limit: 1 }, options)] });
+};
+Collection.prototype.deleteMany = function (filter, options) {
+    return __run({ delete: this.name, deletes: [Object.assign({ q: filter, limit: 0 }, options)] });
+};
+Collection.prototype.__findAndModify = function (filter, fields, options) {
+    options = Object.assign({}, options);
+    var command = Object.assign({ findAndModify: this.name, query: filter }, fields);
+    if (options.returnDocument !== undefined) {
+        command.new = options.returnDocument === "after";
+        delete options.returnDocument;
+    }
+    if (options.projection !== undefined) {
+        command.fields = options.projection;
+        delete options.projection;
+    }
+    var res = __run(Object.assign(command, options));
+    return __online() ? res.value : res;
+};
+Collection.prototype.findOneAndUpdate = function (filter, update, options) {
+    return this.__findAndModify(filter, { update: update }, options);
+};
+Collection.prototype.findOneAndReplace = function (filter, replacement, options) {
+    return this.__findAndModify(filter, { update: replacement }, options);
+};
+Collection.prototype.findOneAndDelete = function (filter, options) {
+    return this.__findAndModify(filter, { remove: true }, options);
+};
+Collection.prototype.bulkWrite = __unsupported("bulkWrite", "insertMany, updateMany or deleteMany");
+Collection.prototype.createIndex = function (keys, options) {
+    return this.createIndexes([keys], options);
+};
+Collection.prototype.createIndexes = function (keyPatterns, options) {
+    var indexes = keyPatterns.map(function (keys) {
+        return Object.assign({ key: keys, name: __indexName(keys) }, options);
+    });
+    return __run({ createIndexes: this.name, indexes: indexes });
+};
+Collection.prototype.dropIndex = function (index) {
+    return __run({ dropIndexes: this.name, index: index });
+};
+Collection.prototype.dropIndexes = function (indexes) {
+    return __run({ dropIndexes: this.name, index: indexes === undefined ? "*" : indexes });
+};
+// pipelines ending with `$out` or `$merge` are writes, so they are recorded without database
+Collection.prototype.aggregate = function (pipeline, options) {
+    var command = Object.assign({ aggregate: this.name, pipeline: pipeline, cursor: {} }, options);
+    var last = pipeline.length > 0 ? pipeline[pipeline.length - 1] : {};
+    var cursor = new Cursor(command);
+    if ("$out" in last || "$merge" in last) {
+        __commands.push(__ejson(command));
+        if (__online()) {
+            cursor.__load();
+        }
+    }
+    return cursor;
+};
+Collection.prototype.drop = function () {
+    return __run({ drop: this.name });
+};
+
+var vars = new Proxy(__vars, {
+    get: function (target, property) {
+        if (typeof property === "string" && !(property in target)) {
+            throw new ReferenceError("undefined variable: " + property);
+        }
+        return target[property];
+    },
+});
+
+var db = new Proxy({
+    getName: function () { return __dbName; },
+    getCollection: function (name) { return new Collection(name); },
+    getCollectionNames: function () {
+        return __query({ listCollections: 1, nameOnly: true }).map(function (info) { return info.name; });
+    },
+    getCollectionInfos: function (filter) {
+        return __query({ listCollections: 1, filter: filter === undefined ? {} : filter });
+    },
+    createCollection: function (name, options) {
+        return __run(Object.assign({ create: name }, options));
+    },
+    createView: function (name, source, pipeline, options) {
+        return __run(Object.assign({ create: name, viewOn: source, pipeline: pipeline }, options));
+    },
+    runCommand: function (command) { return __run(command); },
+    stats: function () { return __exec({ dbStats: 1 }); },
+}, {
+    get: function (target, property) {
+        if (property in target || typeof property !== "string") {
+            return target[property];
+        }
+        return new Collection(property);
+    },
+});
+
+function __result() {
+    return {
+        description: description === undefined ? "" : String(description),
+        commands: __commands,
+        output: __output,
+    };
+}
+"#;
+
+// database of running migration, calls are blocking, because script runs synchronously
+pub trait Executor {
+    fn command(&self, command: Document) -> Result<Document>;
+    // every document of cursor command, e.g. `find` or `aggregate`
+    fn query(&self, command: Document) -> Result<Vec<Document>>;
+}
+
+// blocks worker thread of multi-threaded runtime while command runs
+pub(crate) struct DatabaseExecutor {
+    pub(crate) db: Database,
+    pub(crate) base_dir: PathBuf,
+    pub(crate) data_batch_size: usize,
+}
+
+impl DatabaseExecutor {
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        tokio::task::block_in_place(|| Handle::current().block_on(future))
+    }
+}
+
+impl Executor for DatabaseExecutor {
+    fn command(&self, command: Document) -> Result<Document> {
+        if let Some(load) = LoadData::from_command(&command, &self.base_dir)? {
+            self.block_on(insert_data(&self.db, &load, self.data_batch_size))?;
+            return Ok(bson::doc! { "ok": 1 });
+        }
+
+        debug!(
+            "running command: {}",
+            command.keys().next().map_or("", String::as_str)
+        );
+        Ok(self.block_on(self.db.run_command(command).into_future())?)
+    }
+
+    fn query(&self, command: Document) -> Result<Vec<Document>> {
+        self.block_on(async {
+            let cursor = self.db.run_cursor_command(command).await?;
+            Ok(cursor.try_collect().await?)
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct ScriptOutput {
+    output: Vec<String>,
+}
+
+// returned commands are the ones run by script, they must not be run again
+pub fn eval_script(
+    script: &str,
+    vars: &Variables,
+    executor: Option<Rc<dyn Executor>>,
+) -> Result<MigrationContent> {
+    let mut context = Context::default();
+    let js_err = |e: JsError| anyhow!("javascript error: {e}");
+
+    context
+        .runtime_limits_mut()
+        .set_loop_iteration_limit(LOOP_ITERATION_LIMIT);
+
+    if let Some(executor) = executor {
+        register_executor(&mut context, executor).map_err(js_err)?;
+    }
+
+    context
+        .eval(Source::from_bytes(&format!(
+            "var __dbName = {};\nvar __vars = {};\n{PRELUDE}",
+            serde_json::to_string(vars.db_name())?,
+            serde_json::to_string(&vars.to_map())?
+        )))
+        .map_err(js_err)?;
+    let res = context.eval(Source::from_bytes(script)).map_err(js_err);
+
+    // output of failed script helps to find the cause
+    let result = context
+        .eval(Source::from_bytes("__result()"))
+        .map_err(js_err)?
+        .to_json(&mut context)
+        .map_err(js_err)?
+        .ok_or_else(|| anyhow!("javascript migration produced no result"))?;
+
+    for line in serde_json::from_value::<ScriptOutput>(result.clone())?.output {
+        info!("{line}");
+    }
+
+    res?;
+
+    Ok(serde_json::from_value::<MigrationContent>(result)?)
+}
+
+fn register_executor(context: &mut Context, executor: Rc<dyn Executor>) -> JsResult<()> {
+    let fetcher = executor.clone();
+    // SAFETY: closures capture only the executor, it holds no values of garbage collector
+    let execute = unsafe {
+        NativeFunction::from_closure(move |_, args, context| {
+            let reply = executor
+                .command(command_arg(args, context)?)
+                .map_err(native_error)?;
+            to_json_string(&Bson::Document(reply))
+        })
+    };
+    let fetch = unsafe {
+        NativeFunction::from_closure(move |_, args, context| {
+            let docs = fetcher
+                .query(command_arg(args, context)?)
+                .map_err(native_error)?;
+            to_json_string(&Bson::Array(docs.into_iter().map(Bson::Document).collect()))
+        })
+    };
+
+    context.register_global_callable(JsString::from("__execute"), 1, execute)?;
+    context.register_global_callable(JsString::from("__fetch"), 1, fetch)
+}
+
+fn command_arg(args: &[JsValue], context: &mut Context) -> JsResult<Document> {
+    let json = args
+        .first()
+        .cloned()
+        .unwrap_or_default()
+        .to_string(context)?
+        .to_std_string_escaped();
+    let value: serde_json::Value = serde_json::from_str(&json).map_err(native_error)?;
+
+    match Bson::try_from(value).map_err(native_error)? {
+        Bson::Document(doc) => Ok(doc),
+        _ => Err(JsNativeError::typ()
+            .with_message("command must be an object")
+            .into()),
+    }
+}
+
+fn to_json_string(value: &Bson) -> JsResult<JsValue> {
+    let json = value.clone().into_relaxed_extjson().to_string();
+    Ok(JsValue::from(JsString::from(json.as_str())))
+}
+
+fn native_error(e: impl std::fmt::Display) -> JsError {
+    JsNativeError::error().with_message(e.to_string()).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::template::tests::vars;
+    use bson::doc;
+    use std::cell::RefCell;
+
+    // replies to reads with the given documents, writes are acknowledged
+    #[derive(Default)]
+    struct FakeExecutor {
+        docs: Vec<Document>,
+        commands: RefCell<Vec<Document>>,
+    }
+
+    impl Executor for FakeExecutor {
+        fn command(&self, command: Document) -> Result<Document> {
+            self.commands.borrow_mut().push(command);
+            Ok(doc! { "ok": 1, "n": 1 })
+        }
+
+        fn query(&self, command: Document) -> Result<Vec<Document>> {
+            self.commands.borrow_mut().push(command);
+            Ok(self.docs.clone())
+        }
+    }
+
+    #[test]
+    fn records_commands_without_database() {
+        let content = eval_script(
+            r#"
+            description = "add index";
+            db.users.createIndex({ email: 1 }, { unique: true });
+            db.getCollection("logs").insertOne({ at: ISODate("2025-01-02T00:00:00Z") });
+            "#,
+            &vars(&[]),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(content.description, "add index");
+        assert_eq!(
+            content.commands[0],
+            doc! {
+                "createIndexes": "users",
+                "indexes": [{ "key": { "email": 1 }, "name": "email_1", "unique": true }],
+            }
+        );
+        assert_eq!(content.commands[1].get_str("insert").unwrap(), "logs");
+        assert!(matches!(
+            content.commands[1].get_array("documents").unwrap()[0]
+                .as_document()
+                .unwrap()
+                .get("at"),
+            Some(Bson::DateTime(_))
+        ));
+    }
+
+    #[test]
+    fn vars_keep_their_types() {
+        let content = eval_script(
+            r#"
+            if (vars.ttl_days + 1 !== 31 || vars.enabled !== true || vars.name !== "app") {
+                throw new Error("wrong vars");
+            }
+            db.runCommand({ ping: vars.ttl_days });
+            "#,
+            &vars(&[
+                ("ttl_days", 30.into()),
+                ("enabled", true.into()),
+                ("name", "app".into()),
+            ]),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(content.commands, vec![doc! { "ping": 30 }]);
+    }
+
+    #[test]
+    fn undefined_var_throws() {
+        let Err(err) = eval_script("vars.missing;", &vars(&[]), None) else {
+            panic!("undefined variable must throw");
+        };
+
+        assert!(format!("{err:#}").contains("undefined variable: missing"));
+    }
+
+    #[test]
+    fn reads_need_database() {
+        for script in [
+            "db.users.find({}).forEach(function (doc) {});",
+            "db.users.findOne();",
+            "db.users.countDocuments();",
+            "db.users.distinct('email');",
+            "db.users.aggregate([{ $match: {} }]).toArray();",
+            "db.runCommand({ ping: 1 }).ok;",
+        ] {
+            assert!(eval_script(script, &vars(&[]), None).is_err(), "{script}");
+        }
+    }
+
+    #[test]
+    fn runaway_loop_is_stopped() {
+        assert!(eval_script("while (true) {}", &vars(&[]), None).is_err());
+    }
+
+    #[test]
+    fn runs_commands_and_reads_with_database() {
+        let executor = Rc::new(FakeExecutor {
+            docs: vec![
+                doc! { "_id": 1, "email": "A@x.io" },
+                doc! { "_id": 2, "email": "B@x.io" },
+            ],
+            ..Default::default()
+        });
+
+        let content = eval_script(
+            r#"
+            db.users.find({}, { email: 1 }).forEach(function (user) {
+                var res = db.users.updateOne(
+                    { _id: user._id },
+                    { $set: { email: user.email.toLowerCase() } }
+                );
+                if (res.n !== 1) {
+                    throw new Error("not updated");
+                }
+            });
+            "#,
+            &vars(&[]),
+            Some(executor.clone()),
+        )
+        .unwrap();
+
+        let commands = executor.commands.borrow();
+        assert_eq!(
+            commands[0],
+            doc! { "find": "users", "filter": {}, "projection": { "email": 1 } }
+        );
+        assert_eq!(
+            commands[2],
+            doc! {
+                "update": "users",
+                "updates": [{ "q": { "_id": 2 }, "u": { "$set": { "email": "b@x.io" } }, "multi": false }],
+            }
+        );
+        assert_eq!(commands.len(), 3);
+        assert_eq!(content.commands, commands[1..]);
+    }
+}
@@ -1,3 +1,22 @@
+mod connection;
+mod data;
 mod ds;
+mod export;
+mod history;
+mod hooks;
+mod indexes;
+mod js;
+mod loader;
+mod lock;
+mod schema;
+mod seed;
+mod snapshot;
+mod squash;
+mod status;
+mod template;
+mod validators;
 
+pub mod diff;
+pub mod lint;
 pub mod migration;
+pub mod report;
@@ -0,0 +1,381 @@
+use anyhow::{Result, anyhow};
+use bson::{Bson, Document};
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    data::LOAD_DATA_COMMAND,
+    ds::{Direction, MigrationContent, TODO_DESCRIPTION},
+    loader::{load_migration, migration_dirs, migration_file},
+    report::{LintFinding, LintReport},
+    template::Variables,
+};
+use momitroll_config::Config;
+
+// database commands which make sense inside migrations
+const KNOWN_COMMANDS: [&str; 51] = [
+    "aggregate",
+    "bulkWrite",
+    "cloneCollectionAsCapped",
+    "collMod",
+    "compact",
+    "configureCollectionBalancing",
+    "convertToCapped",
+    "count",
+    "create",
+    "createIndexes",
+    "createRole",
+    "createSearchIndexes",
+    "createUser",
+    "dbStats",
+    "delete",
+    "distinct",
+    "drop",
+    "dropAllRolesFromDatabase",
+    "dropAllUsersFromDatabase",
+    "dropDatabase",
+    "dropIndexes",
+    "dropRole",
+    "dropSearchIndex",
+    "dropUser",
+    "enableSharding",
+    "explain",
+    "find",
+    "findAndModify",
+    "grantPrivilegesToRole",
+    "grantRolesToRole",
+    "grantRolesToUser",
+    "insert",
+    "listCollections",
+    "listIndexes",
+    "ping",
+    "reIndex",
+    "refineCollectionShardKey",
+    "renameCollection",
+    "reshardCollection",
+    "revokePrivilegesFromRole",
+    "revokeRolesFromRole",
+    "revokeRolesFromUser",
+    "setFeatureCompatibilityVersion",
+    "setIndexCommitQuorum",
+    "shardCollection",
+    "update",
+    "updateRole",
+    "updateSearchIndex",
+    "updateUser",
+    "validate",
+    LOAD_DATA_COMMAND,
+];
+
+// commands which lose data or schema, they can't be undone without down migration
+const DESTRUCTIVE_COMMANDS: [&str; 6] = [
+    "delete",
+    "drop",
+    "dropDatabase",
+    "dropIndexes",
+    "dropSearchIndex",
+    "renameCollection",
+];
+
+pub fn lint(config: &Config) -> Result<LintReport> {
+    let migration_dir = Path::new(&config.migration.dir);
+
+    if !migration_dir.exists() {
+        return Err(anyhow!(
+            "migration directory doesn't exist: {}",
+            migration_dir.display()
+        ));
+    }
+
+    let mut findings = vec![];
+
+    for name in migration_dirs(&config.migration.dir)? {
+        lint_migration(config, &name, &mut findings);
+    }
+
+    Ok(LintReport { findings })
+}
+
+// file of migration, error of loading its content, or error of finding it
+type Loaded = Result<(PathBuf, Result<MigrationContent>)>;
+
+fn lint_migration(config: &Config, name: &str, findings: &mut Vec<LintFinding>) {
+    let vars = Variables::new(config, Some(name));
+    let load = |direction| -> Loaded {
+        let path = migration_file(&config.migration.dir, name, direction)?;
+        let content = load_migration(&path, &vars);
+        Ok((path, content))
+    };
+
+    check_migration(name, load(Direction::Up), load(Direction::Down), findings);
+}
+
+fn check_migration(name: &str, up: Loaded, down: Loaded, findings: &mut Vec<LintFinding>) {
+    let mut finding = |file: Option<&PathBuf>, rule, message: String| {
+        findings.push(LintFinding {
+            migration: name.to_string(),
+            file: file.map(|path| path.display().to_string()),
+            rule,
+            message,
+        })
+    };
+
+    let up = match up {
+        Ok((path, Ok(content))) => Some((path, content)),
+        Ok((path, Err(e))) => {
+            finding(Some(&path), "invalid-file", format!("{e:#}"));
+            None
+        }
+        Err(e) => {
+            finding(None, "missing-up", e.to_string());
+            None
+        }
+    };
+    let down = match down {
+        Ok((path, Ok(content))) => Some((path, content)),
+        Ok((path, Err(e))) => {
+            finding(Some(&path), "invalid-file", format!("{e:#}"));
+            None
+        }
+        Err(e) => {
+            finding(None, "missing-down", e.to_string());
+            None
+        }
+    };
+
+    for (path, content) in up.iter().chain(down.iter()) {
+        if content.description.trim().is_empty() || content.description == TODO_DESCRIPTION {
+            finding(
+                Some(path),
+                "todo-description",
+                "description is empty or not edited".to_string(),
+            );
+        }
+
+        for (i, command) in content.commands.iter().enumerate() {
+            match command.keys().next() {
+                Some(command_name) if KNOWN_COMMANDS.contains(&command_name.as_str()) => {}
+                Some(command_name) => finding(
+                    Some(path),
+                    "unknown-command",
+                    format!("command #{i} has unknown name `{command_name}`"),
+                ),
+                None => finding(
+                    Some(path),
+                    "unknown-command",
+                    format!("command #{i} is empty"),
+                ),
+            }
+        }
+    }
+
+    if let Some((path, content)) = &up {
+        if content.commands.is_empty() {
+            finding(
+                Some(path),
+                "empty-commands",
+                "up migration has no commands".to_string(),
+            );
+        }
+
+        let destructive = content
+            .commands
+            .iter()
+            .filter_map(|command| command.keys().next())
+            .filter(|command_name| DESTRUCTIVE_COMMANDS.contains(&command_name.as_str()))
+            .collect::<BTreeSet<_>>();
+        let has_down = down
+            .as_ref()
+            .is_some_and(|(_, down)| !down.commands.is_empty());
+
+        if !destructive.is_empty() && !has_down {
+            finding(
+                Some(path),
+                "destructive-without-down",
+                format!(
+                    "destructive commands without down migration: {}",
+                    destructive
+                        .into_iter()
+                        .cloned()
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            );
+        }
+    }
+
+    if let (Some((up_path, up)), Some((_, down))) = (&up, &down) {
+        if down.commands.is_empty() {
+            return;
+        }
+
+        let up_collections = collections(&up.commands);
+        let down_collections = collections(&down.commands);
+
+        if up_collections != down_collections {
+            finding(
+                Some(up_path),
+                "collection-mismatch",
+                format!(
+                    "up touches [{}], down touches [{}]",
+                    up_collections.into_iter().collect::<Vec<_>>().join(", "),
+                    down_collections.into_iter().collect::<Vec<_>>().join(", ")
+                ),
+            );
+        }
+    }
+}
+
+// value of the command name is a collection for collection level commands
+fn collections(commands: &[Document]) -> BTreeSet<String> {
+    commands
+        .iter()
+        .filter_map(|command| match command.iter().next()? {
+            (name, Bson::Document(spec)) if name == LOAD_DATA_COMMAND => {
+                spec.get_str("collection").ok().map(str::to_string)
+            }
+            (_, Bson::String(collection)) => Some(collection.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bson::doc;
+
+    fn file(direction: &str, description: &str, commands: Vec<Document>) -> Loaded {
+        Ok((
+            PathBuf::from(format!("m/m_{direction}.json")),
+            Ok(MigrationContent {
+                description: description.to_string(),
+                commands,
+            }),
+        ))
+    }
+
+    fn rules(up: Loaded, down: Loaded) -> Vec<&'static str> {
+        let mut findings = vec![];
+
+        check_migration("m", up, down, &mut findings);
+
+        findings.into_iter().map(|finding| finding.rule).collect()
+    }
+
+    fn create() -> Vec<Document> {
+        vec![doc! { "create": "users" }]
+    }
+
+    fn drop() -> Vec<Document> {
+        vec![doc! { "drop": "users" }]
+    }
+
+    #[test]
+    fn valid_migration_has_no_findings() {
+        assert!(
+            rules(
+                file("up", "add users", create()),
+                file("down", "drop users", drop())
+            )
+            .is_empty()
+        );
+    }
+
+    #[test]
+    fn missing_files_are_reported() {
+        assert_eq!(
+            rules(Err(anyhow!("no up")), file("down", "drop users", drop())),
+            ["missing-up"]
+        );
+        assert_eq!(
+            rules(file("up", "add users", create()), Err(anyhow!("no down"))),
+            ["missing-down"]
+        );
+    }
+
+    #[test]
+    fn invalid_file_is_reported() {
+        let invalid = Ok((PathBuf::from("m/m_up.json"), Err(anyhow!("bad json"))));
+
+        assert_eq!(
+            rules(invalid, file("down", "drop users", drop())),
+            ["invalid-file"]
+        );
+    }
+
+    #[test]
+    fn todo_description_is_reported() {
+        assert_eq!(
+            rules(
+                file("up", TODO_DESCRIPTION, create()),
+                file("down", " ", drop())
+            ),
+            ["todo-description", "todo-description"]
+        );
+    }
+
+    #[test]
+    fn unknown_command_is_reported() {
+        assert_eq!(
+            rules(
+                file("up", "add users", vec![doc! { "crate": "users" }, doc! {}]),
+                file("down", "drop users", vec![])
+            ),
+            ["unknown-command", "unknown-command"]
+        );
+    }
+
+    #[test]
+    fn empty_up_is_reported() {
+        assert_eq!(
+            rules(
+                file("up", "nothing", vec![]),
+                file("down", "nothing", vec![])
+            ),
+            ["empty-commands"]
+        );
+    }
+
+    #[test]
+    fn destructive_without_down_is_reported() {
+        assert_eq!(
+            rules(
+                file("up", "drop users", drop()),
+                file("down", "nothing", vec![])
+            ),
+            ["destructive-without-down"]
+        );
+        assert!(
+            rules(
+                file("up", "drop users", drop()),
+                file("down", "add users", create())
+            )
+            .is_empty()
+        );
+    }
+
+    #[test]
+    fn collection_mismatch_is_reported() {
+        assert_eq!(
+            rules(
+                file("up", "add users", create()),
+                file("down", "drop logs", vec![doc! { "drop": "logs" }])
+            ),
+            ["collection-mismatch"]
+        );
+        assert!(
+            rules(
+                file(
+                    "up",
+                    "load users",
+                    vec![doc! { LOAD_DATA_COMMAND: { "collection": "users", "file": "u.json" } }]
+                ),
+                file("down", "drop users", drop())
+            )
+            .is_empty()
+        );
+    }
+}
@@ -0,0 +1,316 @@
+use anyhow::{Context, Result, anyhow};
+use serde::de::DeserializeOwned;
+use std::{
+    collections::BTreeSet,
+    fs::read_dir,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use crate::{
+    data::LoadData,
+    ds::{Direction, MigrationContent, MigrationFormat},
+    js::{Executor, eval_script},
+    template::{Variables, render},
+};
+
+// names of migrations, sorted by their timestamp prefix
+pub fn migration_dirs(dir: &str) -> Result<BTreeSet<String>> {
+    let mut names = BTreeSet::new();
+
+    for entry in read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+
+        // archive of squashed migrations and other hidden directories
+        if entry.file_type()?.is_dir() && !name.starts_with('.') {
+            names.insert(name);
+        }
+    }
+
+    Ok(names)
+}
+
+pub fn migration_file(dir: &str, name: &str, direction: Direction) -> Result<PathBuf> {
+    let base = format!("{dir}/{name}/{name}_{}", direction.as_str());
+
+    MigrationFormat::EXTENSIONS
+        .into_iter()
+        .map(|ext| PathBuf::from(format!("{base}.{ext}")))
+        .find(|path| path.exists())
+        .ok_or_else(|| {
+            anyhow!(
+                "migration file {base}.{{{}}} doesn't exist",
+                MigrationFormat::EXTENSIONS.join(",")
+            )
+        })
+}
+
+pub fn load_migration<P: AsRef<Path>>(path: P, vars: &Variables) -> Result<MigrationContent> {
+    let path = path.as_ref();
+    let format = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .and_then(MigrationFormat::from_extension)
+        .ok_or_else(|| anyhow!("unsupported migration file: {}", path.display()))?;
+    let source = match format {
+        MigrationFormat::Js => std::fs::read_to_string(path)?,
+        _ => render(&std::fs::read_to_string(path)?, vars)
+            .with_context(|| format!("failed to render migration file {}", path.display()))?,
+    };
+
+    match format {
+        MigrationFormat::Js => eval_script(&source, vars, None),
+        _ => parse(&source, format),
+    }
+    .with_context(|| format!("failed to load migration file {}", path.display()))
+}
+
+// javascript migration runs its commands itself, other formats return none
+pub fn run_script<P: AsRef<Path>>(
+    path: P,
+    vars: &Variables,
+    executor: Rc<dyn Executor>,
+) -> Result<Option<MigrationContent>> {
+    let path = path.as_ref();
+
+    if is_data_file(path) {
+        return Ok(None);
+    }
+
+    eval_script(&std::fs::read_to_string(path)?, vars, Some(executor))
+        .map(Some)
+        .with_context(|| format!("failed to run migration file {}", path.display()))
+}
+
+// seed manifests, validators and index definitions share migration data formats, except js
+pub fn load_file<T: DeserializeOwned, P: AsRef<Path>>(path: P) -> Result<T> {
+    let path = path.as_ref();
+    let format = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .and_then(MigrationFormat::from_extension)
+        .ok_or_else(|| anyhow!("unsupported file: {}", path.display()))?;
+
+    parse(&std::fs::read_to_string(path)?, format)
+        .with_context(|| format!("failed to load {}", path.display()))
+}
+
+pub fn is_data_file<P: AsRef<Path>>(path: P) -> bool {
+    path.as_ref()
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .and_then(MigrationFormat::from_extension)
+        .is_some_and(|format| !matches!(format, MigrationFormat::Js))
+}
+
+pub fn find_file<P: AsRef<Path>>(base: P) -> Option<PathBuf> {
+    let base = base.as_ref().display().to_string();
+
+    MigrationFormat::EXTENSIONS
+        .into_iter()
+        .filter(|ext| {
+            !matches!(
+                MigrationFormat::from_extension(ext),
+                Some(MigrationFormat::Js)
+            )
+        })
+        .map(|ext| PathBuf::from(format!("{base}.{ext}")))
+        .find(|path| path.exists())
+}
+
+fn parse<T: DeserializeOwned>(source: &str, format: MigrationFormat) -> Result<T> {
+    match format {
+        MigrationFormat::Json => Ok(serde_json::from_str(source)?),
+        MigrationFormat::Json5 => Ok(json5::from_str(source)?),
+        MigrationFormat::Yaml => Ok(serde_yaml::from_str(source)?),
+        MigrationFormat::Toml => {
+            let table: toml::Table = toml::from_str(source)?;
+            Ok(serde_json::from_value(toml_to_json(toml::Value::Table(
+                table,
+            ))?)?)
+        }
+        MigrationFormat::Js => Err(anyhow!("javascript is supported only in migrations")),
+    }
+}
+
+// toml datetimes become extended json dates, local ones are taken as utc
+fn toml_to_json(value: toml::Value) -> Result<serde_json::Value> {
+    use serde_json::{Value, json};
+
+    Ok(match value {
+        toml::Value::String(v) => Value::String(v),
+        toml::Value::Integer(v) => json!(v),
+        toml::Value::Float(v) => json!(v),
+        toml::Value::Boolean(v) => Value::Bool(v),
+        toml::Value::Array(values) => Value::Array(
+            values
+                .into_iter()
+                .map(toml_to_json)
+                .collect::<Result<_>>()?,
+        ),
+        toml::Value::Table(table) => Value::Object(
+            table
+                .into_iter()
+                .map(|(key, value)| Ok((key, toml_to_json(value)?)))
+                .collect::<Result<_>>()?,
+        ),
+        toml::Value::Datetime(dt) => {
+            let date = match (dt.date, dt.time, dt.offset) {
+                (Some(_), Some(_), Some(_)) => dt.to_string(),
+                (Some(_), Some(_), None) => format!("{dt}Z"),
+                (Some(date), None, None) => format!("{date}T00:00:00Z"),
+                _ => return Err(anyhow!("time without date can't be stored: {dt}")),
+            };
+            json!({ "$date": date })
+        }
+    })
+}
+
+// raw bytes of migration file and every data file it loads, stored as int to match changelog schema
+pub fn checksum<P: AsRef<Path>>(path: P, content: &MigrationContent) -> Result<i32> {
+    use crc32fast::Hasher;
+    use std::io::Read;
+
+    let path = path.as_ref();
+    let base_dir = path.parent().unwrap_or(Path::new("."));
+    let mut files = vec![path.to_path_buf()];
+    let mut hasher = Hasher::new();
+    let mut buf = vec![0; 64 * 1024];
+
+    for command in &content.commands {
+        if let Some(load) = LoadData::from_command(command, base_dir)? {
+            files.push(load.file);
+        }
+    }
+
+    for file in files {
+        let mut file = std::fs::File::open(&file)
+            .with_context(|| format!("failed to open {}", file.display()))?;
+
+        loop {
+            match file.read(&mut buf)? {
+                0 => break,
+                n => hasher.update(&buf[..n]),
+            }
+        }
+    }
+
+    Ok(hasher.finalize() as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bson::{Bson, doc, oid::ObjectId};
+
+    #[test]
+    fn extended_json_is_decoded_in_every_format() {
+        let sources = [
+            (
+                MigrationFormat::Json,
+                r#"{"description": "", "commands": [{"insert": "users", "documents": [
+                    {"_id": {"$oid": "64b7f0c2a1b2c3d4e5f60718"},
+                     "at": {"$date": "2025-01-02T03:04:05Z"},
+                     "n": {"$numberLong": "5"}}]}]}"#,
+            ),
+            (
+                MigrationFormat::Json5,
+                r#"{description: "", commands: [{insert: "users", documents: [
+                    {_id: {$oid: "64b7f0c2a1b2c3d4e5f60718"},
+                     at: {$date: "2025-01-02T03:04:05Z"},
+                     n: {$numberLong: "5"}}]}]}"#,
+            ),
+            (
+                MigrationFormat::Yaml,
+                r#"
+description: ""
+commands:
+  - insert: users
+    documents:
+      - _id: { $oid: 64b7f0c2a1b2c3d4e5f60718 }
+        at: { $date: "2025-01-02T03:04:05Z" }
+        n: { $numberLong: "5" }
+"#,
+            ),
+            (
+                MigrationFormat::Toml,
+                r#"
+description = ""
+
+[[commands]]
+insert = "users"
+
+[[commands.documents]]
+_id = { "$oid" = "64b7f0c2a1b2c3d4e5f60718" }
+at = { "$date" = "2025-01-02T03:04:05Z" }
+n = { "$numberLong" = "5" }
+"#,
+            ),
+        ];
+        let expected = doc! {
+            "_id": ObjectId::parse_str("64b7f0c2a1b2c3d4e5f60718").unwrap(),
+            "at": bson::DateTime::parse_rfc3339_str("2025-01-02T03:04:05Z").unwrap(),
+            "n": 5_i64,
+        };
+
+        for (format, source) in sources {
+            let content: MigrationContent = parse(source, format).unwrap();
+
+            assert_eq!(
+                content.commands[0].get_array("documents").unwrap()[0],
+                Bson::Document(expected.clone()),
+                "{}",
+                format.extension()
+            );
+        }
+    }
+
+    #[test]
+    fn toml_datetimes_are_dates() {
+        let content: MigrationContent = parse(
+            r#"
+description = ""
+
+[[commands]]
+insert = "events"
+documents = [
+    { at = 2025-01-02T03:04:05+02:00 },
+    { at = 2025-01-02T03:04:05 },
+    { at = 2025-01-02 },
+]
+"#,
+            MigrationFormat::Toml,
+        )
+        .unwrap();
+        let dates = content.commands[0]
+            .get_array("documents")
+            .unwrap()
+            .iter()
+            .map(|doc| {
+                doc.as_document()
+                    .unwrap()
+                    .get_datetime("at")
+                    .unwrap()
+                    .to_owned()
+            })
+            .map(|date| date.try_to_rfc3339_string().unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            dates,
+            [
+                "2025-01-02T01:04:05Z",
+                "2025-01-02T03:04:05Z",
+                "2025-01-02T00:00:00Z"
+            ]
+        );
+        assert!(
+            parse::<MigrationContent>(
+                "description = \"\"\ncommands = [{ at = 03:04:05 }]",
+                MigrationFormat::Toml
+            )
+            .is_err()
+        );
+    }
+}
@@ -0,0 +1,66 @@
+use anyhow::{Result, anyhow};
+use bson::{DateTime, Document, doc};
+use mongodb::error::{ErrorKind as DbErrorKind, WriteFailure};
+use tracing::{debug, warn};
+
+use crate::migration::MigrationController;
+use momitroll_util::error::ErrorKind;
+
+const LOCK_ID: &str = "migration";
+const DUPLICATE_KEY_CODE: i32 = 11000;
+
+impl MigrationController {
+    // single document with fixed `_id`, unique index of `_id` makes insert atomic
+    pub(crate) async fn acquire_lock(&self) -> Result<()> {
+        let collection = self
+            .db
+            .collection::<Document>(&self.config.migration.lock_coll_name());
+        let lock = doc! {
+            "_id": LOCK_ID,
+            "locked_at": DateTime::now(),
+            "pid": std::process::id(),
+        };
+
+        match collection.insert_one(lock).await {
+            Ok(_) => {
+                debug!("migration lock acquired");
+                Ok(())
+            }
+            Err(e) if is_duplicate_key(&e) => {
+                let locked_at = collection
+                    .find_one(doc! { "_id": LOCK_ID })
+                    .await?
+                    .and_then(|lock| lock.get_datetime("locked_at").ok().copied())
+                    .map(|locked_at| locked_at.to_string())
+                    .unwrap_or_else(|| "unknown time".to_string());
+
+                Err(anyhow!(
+                    "another migration is running since {locked_at}, remove document `{LOCK_ID}` from {} if it's stale",
+                    self.config.migration.lock_coll_name()
+                )
+                .context(ErrorKind::Lock))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub(crate) async fn release_lock(&self) {
+        let res = self
+            .db
+            .collection::<Document>(&self.config.migration.lock_coll_name())
+            .delete_one(doc! { "_id": LOCK_ID })
+            .await;
+
+        match res {
+            Ok(_) => debug!("migration lock released"),
+            Err(e) => warn!("failed to release migration lock: {e}"),
+        }
+    }
+}
+
+fn is_duplicate_key(error: &mongodb::error::Error) -> bool {
+    matches!(
+        error.kind.as_ref(),
+        DbErrorKind::Write(WriteFailure::WriteError(e)) if e.code == DUPLICATE_KEY_CODE
+    )
+}
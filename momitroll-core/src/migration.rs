@@ -5,32 +5,38 @@ use mongodb::Database;
 use std::{
     fs::{File, create_dir_all},
     path::Path,
+    rc::Rc,
+};
+use tracing::{Instrument, debug, info, info_span, warn};
+
+use crate::{
+    connection::connect,
+    data::LoadData,
+    ds::*,
+    hooks::{HookContext, HookPoint},
+    js::DatabaseExecutor,
+    loader::{checksum, load_migration, migration_file, run_script},
+    report::{CreateReport, DropReport, RunEntry, RunReport},
+    template::Variables,
 };
-use tracing::{info, warn};
 
-use crate::ds::*;
+pub use crate::{
+    ds::MigrationFormat,
+    export::{ExportFormat, ExportOptions},
+    history::HistoryFilter,
+    status::StatusFilter,
+};
 use momitroll_config::Config;
-use momitroll_util::{db::helthcheck, file::check_file};
+use momitroll_util::error::ErrorKind;
 
 pub struct MigrationController {
-    db: Database,
-    config: Config,
+    pub(crate) db: Database,
+    pub(crate) config: Config,
 }
 
 impl MigrationController {
     pub async fn new(config: Config) -> Result<Self> {
-        use mongodb::Client;
-
-        let (username, password) = config.creds_env_vars.get_creds()?;
-
-        let db = Client::with_uri_str(&format!(
-            "mongodb://{}:{}@{}:{}/?authSource=admin",
-            username, password, config.db.host, config.db.port
-        ))
-        .await?
-        .database(&config.db.name);
-
-        helthcheck(&db).await?;
+        let db = connect(&config).await?;
 
         Ok(Self { db, config })
     }
@@ -75,6 +81,22 @@ impl MigrationController {
                             "description": {
                                 "bsonType": ["string", "null"],
                                 "description": "description of migration"
+                            },
+                            "failed_at": {
+                                "bsonType": ["date", "null"],
+                                "description": "date of last failure, cleared when migration is applied"
+                            },
+                            "error": {
+                                "bsonType": ["string", "null"],
+                                "description": "error of last failure"
+                            },
+                            "run_at": {
+                                "bsonType": ["date", "null"],
+                                "description": "start of run which applied migration"
+                            },
+                            "duration_ms": {
+                                "bsonType": ["long", "int", "null"],
+                                "description": "time of applying migration in milliseconds"
                             }
                         }
                     }
@@ -89,7 +111,38 @@ impl MigrationController {
         Ok(())
     }
 
-    pub async fn create(&self, name: &str) -> Result<()> {
+    pub async fn create(&self, name: &str, format: MigrationFormat) -> Result<CreateReport> {
+        use chrono::Utc;
+
+        self.check_migration_collection().await?;
+
+        let name = format!("{}_{name}", Utc::now().timestamp());
+        let dir_path = format!("{}/{name}", self.config.migration.dir);
+        let mut files = vec![];
+
+        create_dir_all(&dir_path)?;
+        for direction in [Direction::Up, Direction::Down] {
+            let file = format!(
+                "{dir_path}/{name}_{}.{}",
+                direction.as_str(),
+                format.extension()
+            );
+            Self::init_migration_file(&file, format)?;
+            files.push(file);
+        }
+
+        self.insert_migration(&name).await?;
+
+        Ok(CreateReport { name, files })
+    }
+
+    // used by commands which generate migrations, e.g. `validators generate`
+    pub(crate) async fn create_generated(
+        &self,
+        name: &str,
+        up: MigrationContent,
+        down: MigrationContent,
+    ) -> Result<()> {
         use chrono::Utc;
 
         self.check_migration_collection().await?;
@@ -98,12 +151,16 @@ impl MigrationController {
         let dir_path = format!("{}/{name}", self.config.migration.dir);
 
         create_dir_all(&dir_path)?;
-        Self::init_migration_file(format!("{dir_path}/{name}_up.json"))?;
-        Self::init_migration_file(format!("{dir_path}/{name}_down.json"))?;
+        Self::write_migration_file(format!("{dir_path}/{name}_up.json"), &up)?;
+        Self::write_migration_file(format!("{dir_path}/{name}_down.json"), &down)?;
+
+        self.insert_migration(&name).await
+    }
 
+    async fn insert_migration(&self, name: &str) -> Result<()> {
         self.db
             .collection::<Migration>(&self.config.migration.coll_name())
-            .insert_one(Migration::new(name.clone()))
+            .insert_one(Migration::new(name.to_string()))
             .await?;
 
         info!("migration created: {name}");
@@ -111,127 +168,314 @@ impl MigrationController {
         Ok(())
     }
 
-    pub async fn up(&self) -> Result<()> {
+    pub async fn up(&self, dry_run: bool) -> Result<RunReport> {
+        self.check_migration_collection().await?;
+
+        if dry_run {
+            return self.up_dry_run().await;
+        }
+
+        self.acquire_lock().await?;
+        self.sync_history_ttl().await;
+
+        let mut ctx = HookContext::new(Direction::Up);
+        let res = match self.up_with_hooks(&mut ctx).await {
+            Err(e) => {
+                self.run_failure_hooks(&ctx, &e).await;
+                Err(e.context(ErrorKind::Migration))
+            }
+            Ok(migrations) if self.config.migration.snapshot => {
+                self.snapshot().await.map(|_| migrations)
+            }
+            Ok(migrations) => Ok(migrations),
+        };
+
+        self.release_lock().await;
+
+        Ok(RunReport {
+            direction: Direction::Up.as_str(),
+            dry_run: false,
+            migrations: res?,
+        })
+    }
+
+    async fn up_with_hooks(&self, ctx: &mut HookContext) -> Result<Vec<RunEntry>> {
         use bson::DateTime;
-        use chrono::Local;
 
-        self.check_migration_collection().await?;
+        self.run_hooks(HookPoint::BeforeUp, ctx).await?;
+
+        let run_at = DateTime::now();
 
-        let collection = self
+        let mut res = self
             .db
-            .collection::<Migration>(&self.config.migration.coll_name());
-        let mut res = collection.find(doc! {}).sort(doc! { "name": 1 }).await?;
+            .collection::<Migration>(&self.config.migration.coll_name())
+            .find(status_filter(MigrationStatus::Pending))
+            .sort(doc! { "name": 1 })
+            .await?;
+        let mut applied = vec![];
 
         while let Some(migration) = res.next().await {
             let migration = migration?;
-            let migration_name = migration.name.clone();
-            let file_path = format!(
-                "{}/{migration_name}/{migration_name}_up.json",
-                self.config.migration.dir
-            );
+            let span = info_span!("migration", name = %migration.name);
+
+            applied.push(self.up_one(migration, run_at, ctx).instrument(span).await?);
+        }
 
-            check_file(&file_path)?;
+        ctx.migration = None;
+        ctx.description = None;
+        self.run_hooks(HookPoint::AfterUp, ctx).await?;
+
+        Ok(applied)
+    }
 
-            let migration_description = self.apply_commands(&file_path).await?;
+    // history event covers hooks and loading of file too, so every failed step is recorded
+    async fn up_one(
+        &self,
+        migration: Migration,
+        run_at: bson::DateTime,
+        ctx: &mut HookContext,
+    ) -> Result<RunEntry> {
+        let name = migration.name.clone();
+        let started_at = bson::DateTime::now();
+        let started = std::time::Instant::now();
+        let res = self.up_step(migration, run_at, ctx).await;
+
+        self.record_history(
+            &name,
+            Direction::Up,
+            started_at,
+            started.elapsed().as_millis() as i64,
+            res.as_ref().map(|(_, checksum)| Some(*checksum)),
+        )
+        .await;
+
+        res.map(|(entry, _)| entry)
+    }
+
+    async fn up_step(
+        &self,
+        migration: Migration,
+        run_at: bson::DateTime,
+        ctx: &mut HookContext,
+    ) -> Result<(RunEntry, i32)> {
+        use bson::DateTime;
+        use std::time::Instant;
+
+        let migration_name = migration.name.clone();
+        ctx.migration = Some(migration_name.clone());
+        ctx.description = migration.description.clone();
+
+        let file_path = migration_file(&self.config.migration.dir, &migration_name, Direction::Up)?;
+
+        self.run_hooks(HookPoint::BeforeEach, ctx).await?;
+
+        let started = Instant::now();
+        let res = self
+            .apply_commands(&file_path, Some(&migration_name))
+            .await
+            .and_then(|content| Ok((checksum(&file_path, &content)?, content)));
+        let duration_ms = started.elapsed().as_millis() as i64;
+        let (checksum, content) = match res {
+            Ok(res) => res,
+            Err(e) => {
+                self.record_failure(&migration_name, &e).await;
+                return Err(e);
+            }
+        };
 
-            collection
-                .update_one(
-                    doc! { "name": migration_name },
-                    doc! { "$set": {
-                        "applied_at": DateTime::from_chrono(Local::now()),
+        self.db
+            .collection::<Migration>(&self.config.migration.coll_name())
+            .update_one(
+                doc! { "name": migration_name },
+                doc! {
+                    "$set": {
+                        "applied_at": DateTime::now(),
                         "status": "applied",
-                        "description": migration_description,
-                    } },
-                )
-                .await?;
+                        "description": &content.description,
+                        "checksum": checksum,
+                        "run_at": run_at,
+                        "duration_ms": duration_ms,
+                    },
+                    "$unset": { "failed_at": "", "error": "" },
+                },
+            )
+            .await?;
 
-            info!("applied migration: {}", migration.name);
-        }
+        info!("applied migration: {}", migration.name);
 
-        Ok(())
+        ctx.description = Some(content.description.clone());
+        self.run_hooks(HookPoint::AfterEach, ctx).await?;
+
+        Ok((
+            RunEntry {
+                name: migration.name,
+                description: Some(content.description),
+                duration_ms: Some(duration_ms),
+                commands: None,
+            },
+            checksum,
+        ))
     }
 
-    pub async fn down(&self) -> Result<()> {
-        use bson::Bson;
+    async fn up_dry_run(&self) -> Result<RunReport> {
+        let mut res = self
+            .db
+            .collection::<Migration>(&self.config.migration.coll_name())
+            .find(status_filter(MigrationStatus::Pending))
+            .sort(doc! { "name": 1 })
+            .await?;
+        let mut migrations = vec![];
+
+        while let Some(migration) = res.next().await {
+            migrations.push(self.dry_run_entry(&migration?.name, Direction::Up)?);
+        }
+
+        Ok(RunReport {
+            direction: Direction::Up.as_str(),
+            dry_run: true,
+            migrations,
+        })
+    }
 
+    pub async fn down(&self, dry_run: bool) -> Result<RunReport> {
         self.check_migration_collection().await?;
 
+        if dry_run {
+            let migrations = match self
+                .find_one(MigrationStatus::Applied, doc! { "applied_at": -1 })
+                .await?
+            {
+                Some(migration) => vec![self.dry_run_entry(&migration.name, Direction::Down)?],
+                None => {
+                    warn!("can't find last applyed migration");
+                    vec![]
+                }
+            };
+
+            return Ok(RunReport {
+                direction: Direction::Down.as_str(),
+                dry_run: true,
+                migrations,
+            });
+        }
+
+        self.acquire_lock().await?;
+        self.sync_history_ttl().await;
+
+        let mut ctx = HookContext::new(Direction::Down);
+        let res = match self.down_with_hooks(&mut ctx).await {
+            Err(e) => {
+                self.run_failure_hooks(&ctx, &e).await;
+                Err(e.context(ErrorKind::Migration))
+            }
+            Ok(migrations) if self.config.migration.snapshot => {
+                self.snapshot().await.map(|_| migrations)
+            }
+            Ok(migrations) => Ok(migrations),
+        };
+
+        self.release_lock().await;
+
+        Ok(RunReport {
+            direction: Direction::Down.as_str(),
+            dry_run: false,
+            migrations: res?,
+        })
+    }
+
+    async fn down_with_hooks(&self, ctx: &mut HookContext) -> Result<Vec<RunEntry>> {
         match self
             .find_one(MigrationStatus::Applied, doc! { "applied_at": -1 })
             .await?
         {
             Some(migration) => {
-                // TODO: question about moving?
-                let migration_name = migration.name.clone();
-                let file_path = format!(
-                    "{}/{migration_name}/{migration_name}_down.json",
-                    self.config.migration.dir
-                );
-
-                check_file(&file_path)?;
-
-                let _ = self.apply_commands(&file_path).await?;
-
-                self.db
-                    .collection::<Migration>(&self.config.migration.coll_name())
-                    .update_one(
-                        doc! { "name": migration_name },
-                        doc! { "$set": {
-                            "applied_at": Bson::Null,
-                            "status": "pending"
-                        } },
-                    )
-                    .await?;
+                let span = info_span!("migration", name = %migration.name);
 
-                info!("rollbacked migration: {}", migration.name);
+                Ok(vec![self.down_one(migration, ctx).instrument(span).await?])
             }
             None => {
-                warn!("can't find last applyed migration")
+                warn!("can't find last applyed migration");
+
+                Ok(vec![])
             }
         }
+    }
 
-        Ok(())
+    // checksum of event is the one of up file recorded in changelog, like events of `up`
+    async fn down_one(&self, migration: Migration, ctx: &mut HookContext) -> Result<RunEntry> {
+        let name = migration.name.clone();
+        let checksum = migration.checksum;
+        let started_at = bson::DateTime::now();
+        let started = std::time::Instant::now();
+        let res = self.down_step(migration, ctx).await;
+
+        self.record_history(
+            &name,
+            Direction::Down,
+            started_at,
+            started.elapsed().as_millis() as i64,
+            res.as_ref().map(|_| checksum),
+        )
+        .await;
+
+        res
     }
 
-    pub async fn status(&self) -> Result<()> {
-        use colored::Colorize;
+    async fn down_step(&self, migration: Migration, ctx: &mut HookContext) -> Result<RunEntry> {
+        use bson::Bson;
+        use std::time::Instant;
 
-        self.check_migration_collection().await?;
+        // TODO: question about moving?
+        let migration_name = migration.name.clone();
+        ctx.migration = Some(migration_name.clone());
+        ctx.description = migration.description.clone();
 
-        let collection = self
-            .db
-            .collection::<Migration>(&self.config.migration.coll_name());
+        let file_path =
+            migration_file(&self.config.migration.dir, &migration_name, Direction::Down)?;
+
+        self.run_hooks(HookPoint::BeforeEach, ctx).await?;
 
-        if collection.count_documents(doc! {}).await? == 0 {
-            warn!("no migrations found");
-            return Ok(());
+        let started = Instant::now();
+        let res = self.apply_commands(&file_path, Some(&migration_name)).await;
+        let duration_ms = started.elapsed().as_millis() as i64;
+
+        if let Err(e) = res {
+            self.record_failure(&migration_name, &e).await;
+            return Err(e);
         }
 
-        let mut res = collection.find(doc! {}).sort(doc! { "name": -1 }).await?;
+        self.db
+            .collection::<Migration>(&self.config.migration.coll_name())
+            .update_one(
+                doc! { "name": migration_name },
+                doc! {
+                    "$set": {
+                        "applied_at": Bson::Null,
+                        "status": "pending"
+                    },
+                    "$unset": {
+                        "run_at": "",
+                        "duration_ms": "",
+                        "failed_at": "",
+                        "error": "",
+                    },
+                },
+            )
+            .await?;
 
-        while let Some(migration) = res.next().await {
-            let migration = migration?;
-            let date = match migration.applied_at {
-                Some(dt) => dt.to_string().green(),
-                None => "<not applied>".to_string().red(),
-            };
+        info!("rollbacked migration: {}", migration.name);
 
-            println!(
-                "name: {}, applied at: {}, status: {}, description: {}",
-                migration.name.blue(),
-                date,
-                migration.status,
-                migration
-                    .description
-                    .unwrap_or_else(|| "<empty>".to_string())
-                    .cyan(),
-            );
-        }
+        self.run_hooks(HookPoint::AfterEach, ctx).await?;
 
-        Ok(())
+        Ok(RunEntry {
+            name: migration.name,
+            description: migration.description,
+            duration_ms: Some(duration_ms),
+            commands: None,
+        })
     }
 
-    pub async fn drop(&self) -> Result<()> {
+    pub async fn drop(&self) -> Result<DropReport> {
         use std::fs::remove_dir_all;
 
         self.check_migration_collection().await?;
@@ -249,33 +493,42 @@ impl MigrationController {
                 remove_dir_all(format!("{}/{}", self.config.migration.dir, migration.name))?;
 
                 info!("dropped migration: {}", migration.name);
+
+                Ok(DropReport {
+                    dropped: Some(migration.name),
+                })
             }
             None => {
                 warn!("no pending migrations found");
+
+                Ok(DropReport { dropped: None })
             }
         }
-
-        Ok(())
     }
 
-    fn init_migration_file<P: AsRef<Path>>(path: P) -> Result<()> {
+    fn init_migration_file<P: AsRef<Path>>(path: P, format: MigrationFormat) -> Result<()> {
         use std::io::Write;
 
         let mut file = File::create(&path)?;
-        write!(
-            &mut file,
-            r#"{{
-    "description\": "TODO: describe which changes will do this migration",
-    "commands": [
-                    
-    ]
-}}
-            "#
-        )?;
+        write!(&mut file, "{}", format.template())?;
 
         Ok(())
     }
 
+    pub(crate) fn write_migration_file<P: AsRef<Path>>(
+        path: P,
+        content: &MigrationContent,
+    ) -> Result<()> {
+        serde_json::to_writer_pretty(File::create(path)?, content)?;
+
+        Ok(())
+    }
+
+    // collections managed by momitroll itself, they never belong to the user schema
+    pub(crate) fn internal_collections(&self) -> Vec<String> {
+        internal_collections(&self.config)
+    }
+
     async fn find_one(
         &self,
         status: MigrationStatus,
@@ -286,23 +539,95 @@ impl MigrationController {
         Ok(self
             .db
             .collection::<Migration>(&self.config.migration.coll_name())
-            .find_one(doc! { "status": status.as_str() })
+            .find_one(status_filter(status))
             .with_options(FindOneOptions::builder().sort(sort_clause).build())
             .await?)
     }
 
-    async fn apply_commands<P: AsRef<Path>>(&self, path: P) -> Result<String> {
-        use serde_json::from_str;
+    // failure is shown by `status` until migration is applied
+    async fn record_failure(&self, name: &str, error: &anyhow::Error) {
+        use bson::DateTime;
+
+        let res = self
+            .db
+            .collection::<Migration>(&self.config.migration.coll_name())
+            .update_one(
+                doc! { "name": name },
+                doc! { "$set": {
+                    "failed_at": DateTime::now(),
+                    "error": format!("{error:#}"),
+                } },
+            )
+            .await;
+
+        if let Err(e) = res {
+            warn!("failed to record failure of migration {name}: {e}");
+        }
+    }
+
+    fn dry_run_entry(&self, name: &str, direction: Direction) -> Result<RunEntry> {
+        use bson::Bson;
+
+        let file_path = migration_file(&self.config.migration.dir, name, direction)?;
+        let content = load_migration(&file_path, &Variables::new(&self.config, Some(name)))?;
+
+        Ok(RunEntry {
+            name: name.to_string(),
+            description: Some(content.description),
+            duration_ms: None,
+            commands: Some(
+                content
+                    .commands
+                    .into_iter()
+                    .map(|command| Bson::Document(command).into_relaxed_extjson())
+                    .collect(),
+            ),
+        })
+    }
+
+    pub(crate) async fn apply_commands<P: AsRef<Path>>(
+        &self,
+        path: P,
+        migration: Option<&str>,
+    ) -> Result<MigrationContent> {
+        let path = path.as_ref();
+        let base_dir = path.parent().unwrap_or(Path::new("."));
+        let vars = Variables::new(&self.config, migration);
+        let executor = DatabaseExecutor {
+            db: self.db.clone(),
+            base_dir: base_dir.to_path_buf(),
+            data_batch_size: self.config.migration.data_batch_size,
+        };
+
+        if let Some(content) = run_script(path, &vars, Rc::new(executor))? {
+            return Ok(content);
+        }
 
-        let migration_content = from_str::<MigrationContent>(&std::fs::read_to_string(path)?)?;
-        for command in migration_content.commands {
-            self.db.run_command(command).await?;
+        let migration_content = load_migration(path, &vars)?;
+
+        for (index, command) in migration_content.commands.iter().enumerate() {
+            async {
+                match LoadData::from_command(command, base_dir)? {
+                    Some(load) => self.load_data(&load).await?,
+                    None => {
+                        debug!(
+                            "running command: {}",
+                            command.keys().next().map_or("", String::as_str)
+                        );
+                        self.db.run_command(command.clone()).await?;
+                    }
+                }
+
+                anyhow::Ok(())
+            }
+            .instrument(info_span!("command", index))
+            .await?;
         }
 
-        Ok(migration_content.description)
+        Ok(migration_content)
     }
 
-    async fn check_migration_collection(&self) -> Result<()> {
+    pub(crate) async fn check_migration_collection(&self) -> Result<()> {
         if !self
             .is_exist_collection(&self.config.migration.coll_name())
             .await?
@@ -321,3 +646,43 @@ impl MigrationController {
             .any(|n| n == name))
     }
 }
+
+// collections of momitroll itself, they aren't part of schema
+pub(crate) fn internal_collections(config: &Config) -> Vec<String> {
+    vec![
+        config.migration.coll_name(),
+        config.migration.lock_coll_name(),
+        config.migration.history_coll_name(),
+    ]
+}
+
+// changelog records in given status, applied ones must never be run by `up` again
+fn status_filter(status: MigrationStatus) -> Document {
+    doc! { "status": status.as_str() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn up_skips_applied_migrations() {
+        let filter = status_filter(MigrationStatus::Pending);
+        let record = |status| {
+            bson::to_document(&Migration {
+                status,
+                ..Migration::new("20240101000000_init".to_string())
+            })
+            .unwrap()
+        };
+
+        assert_eq!(
+            record(MigrationStatus::Pending).get("status"),
+            filter.get("status")
+        );
+        assert_ne!(
+            record(MigrationStatus::Applied).get("status"),
+            filter.get("status")
+        );
+    }
+}
@@ -0,0 +1,297 @@
+use serde::Serialize;
+use serde_json::Value;
+
+pub use crate::ds::MigrationStatus;
+
+// results of commands, printed as is with `--output json`, field names are stable
+// and new fields are only added
+
+// `status`: {"database", "environment", "timezone", "migrations": [MigrationEntry],
+// "summary": StatusSummary, "last_run": LastRun or null}
+#[derive(Serialize)]
+pub struct StatusReport {
+    pub database: String,
+    pub environment: Option<String>,
+    pub timezone: String,
+    pub migrations: Vec<MigrationEntry>,
+    pub summary: StatusSummary,
+    pub last_run: Option<LastRun>,
+}
+
+// {"name", "state", "status": recorded "pending"|"applied" or null when untracked,
+// "applied_at", "description", "checksum", "duration_ms", "failed_at", "error"},
+// times are rfc 3339 in configured timezone
+#[derive(Serialize)]
+pub struct MigrationEntry {
+    pub name: String,
+    pub state: MigrationState,
+    pub status: Option<MigrationStatus>,
+    pub applied_at: Option<String>,
+    pub description: Option<String>,
+    pub checksum: Option<i32>,
+    pub duration_ms: Option<i64>,
+    pub failed_at: Option<String>,
+    pub error: Option<String>,
+}
+
+// changelog reconciled with migration directory
+#[derive(Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum MigrationState {
+    Pending,
+    Applied,
+    // last run failed on it
+    Failed,
+    // recorded, but its directory or up file is gone
+    MissingFile,
+    // applied, but file checksum differs from recorded one
+    Modified,
+    // pending, but newer migrations are already applied
+    OutOfOrder,
+    // directory without changelog record
+    Untracked,
+}
+
+impl MigrationState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MigrationState::Pending => "pending",
+            MigrationState::Applied => "applied",
+            MigrationState::Failed => "failed",
+            MigrationState::MissingFile => "missing-file",
+            MigrationState::Modified => "modified",
+            MigrationState::OutOfOrder => "out-of-order",
+            MigrationState::Untracked => "untracked",
+        }
+    }
+}
+
+// counts of states over whole changelog, filters don't change them
+#[derive(Serialize, Default)]
+pub struct StatusSummary {
+    pub total: usize,
+    pub pending: usize,
+    pub applied: usize,
+    pub failed: usize,
+    pub missing_file: usize,
+    pub modified: usize,
+    pub out_of_order: usize,
+    pub untracked: usize,
+}
+
+impl StatusSummary {
+    pub fn count(&mut self, state: MigrationState) {
+        self.total += 1;
+
+        *match state {
+            MigrationState::Pending => &mut self.pending,
+            MigrationState::Applied => &mut self.applied,
+            MigrationState::Failed => &mut self.failed,
+            MigrationState::MissingFile => &mut self.missing_file,
+            MigrationState::Modified => &mut self.modified,
+            MigrationState::OutOfOrder => &mut self.out_of_order,
+            MigrationState::Untracked => &mut self.untracked,
+        } += 1;
+    }
+
+    pub fn states(&self) -> [(MigrationState, usize); 7] {
+        [
+            (MigrationState::Applied, self.applied),
+            (MigrationState::Pending, self.pending),
+            (MigrationState::Failed, self.failed),
+            (MigrationState::MissingFile, self.missing_file),
+            (MigrationState::Modified, self.modified),
+            (MigrationState::OutOfOrder, self.out_of_order),
+            (MigrationState::Untracked, self.untracked),
+        ]
+    }
+}
+
+// latest `up` run: {"started_at", "migrations", "duration_ms"}
+#[derive(Serialize)]
+pub struct LastRun {
+    pub started_at: String,
+    pub migrations: usize,
+    pub duration_ms: i64,
+}
+
+// `up` and `down`: {"direction": "up"|"down", "dry_run", "migrations": [RunEntry]}
+#[derive(Serialize)]
+pub struct RunReport {
+    pub direction: &'static str,
+    pub dry_run: bool,
+    pub migrations: Vec<RunEntry>,
+}
+
+// {"name", "description", "duration_ms", "commands"}, duration is missing with dry run,
+// rendered commands are present with dry run only
+#[derive(Serialize)]
+pub struct RunEntry {
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commands: Option<Vec<Value>>,
+}
+
+// `create`: {"name", "files": [path]}
+#[derive(Serialize)]
+pub struct CreateReport {
+    pub name: String,
+    pub files: Vec<String>,
+}
+
+// `drop`: {"dropped": name or null when there is no pending migration}
+#[derive(Serialize)]
+pub struct DropReport {
+    pub dropped: Option<String>,
+}
+
+// `history`: {"timezone", "events": [HistoryEntry]}, newest first
+#[derive(Serialize)]
+pub struct HistoryReport {
+    pub timezone: String,
+    pub events: Vec<HistoryEntry>,
+}
+
+// {"migration", "direction": "up"|"down", "started_at", "ended_at", "duration_ms",
+// "outcome": "success"|"failure", "error", "checksum", "environment", "host", "user",
+// "version"}
+#[derive(Serialize)]
+pub struct HistoryEntry {
+    pub migration: String,
+    pub direction: String,
+    pub started_at: String,
+    pub ended_at: String,
+    pub duration_ms: i64,
+    pub outcome: String,
+    pub error: Option<String>,
+    pub checksum: Option<i32>,
+    pub environment: Option<String>,
+    pub host: Option<String>,
+    pub user: Option<String>,
+    pub version: String,
+}
+
+// `lint`: {"findings": [LintFinding]}
+#[derive(Serialize)]
+pub struct LintReport {
+    pub findings: Vec<LintFinding>,
+}
+
+// {"migration", "file": path or null for whole migration, "rule", "message"}
+#[derive(Serialize)]
+pub struct LintFinding {
+    pub migration: String,
+    pub file: Option<String>,
+    pub rule: &'static str,
+    pub message: String,
+}
+
+// `diff`: {"changes": [SchemaChangeEntry]}, changes turn target into source
+#[derive(Serialize)]
+pub struct DiffReport {
+    pub changes: Vec<SchemaChangeEntry>,
+}
+
+// {"kind": "collection"|"view"|"index", "name", "change": "missing"|"extra"|"changed",
+// "options": [name]}, index name is `<collection>.<index>`, options are changed options
+// of collection or view
+#[derive(Serialize)]
+pub struct SchemaChangeEntry {
+    pub kind: &'static str,
+    pub name: String,
+    pub change: Change,
+    pub options: Vec<String>,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Change {
+    // exists in desired state only
+    Missing,
+    // exists in current state only
+    Extra,
+    Changed,
+}
+
+// `validators diff` and `validators apply`: {"changes": [ValidatorEntry]}, apply reports
+// applied changes only
+#[derive(Serialize)]
+pub struct ValidatorsReport {
+    pub changes: Vec<ValidatorEntry>,
+}
+
+impl ValidatorsReport {
+    // validators of other collections aren't managed, so they aren't drift
+    pub fn has_drift(&self) -> bool {
+        self.changes
+            .iter()
+            .any(|change| change.change != ValidatorChangeState::Unmanaged)
+    }
+}
+
+// {"collection", "change": "missing-collection"|"added"|"changed"|"unmanaged",
+// "parts": [changed part of validator]}
+#[derive(Serialize)]
+pub struct ValidatorEntry {
+    pub collection: String,
+    pub change: ValidatorChangeState,
+    pub parts: Vec<&'static str>,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ValidatorChangeState {
+    MissingCollection,
+    Added,
+    Changed,
+    Unmanaged,
+}
+
+// `indexes plan`: {"changes": [IndexEntry]}
+#[derive(Serialize)]
+pub struct IndexesReport {
+    pub changes: Vec<IndexEntry>,
+}
+
+// {"collection", "name", "change": "missing"|"extra"|"changed", "index": desired definition
+// or current one of extra index}
+#[derive(Serialize)]
+pub struct IndexEntry {
+    pub collection: String,
+    pub name: String,
+    pub change: Change,
+    pub index: Option<Value>,
+}
+
+// `seed`: {"collections": [SeedEntry]}
+#[derive(Serialize)]
+pub struct SeedReport {
+    pub collections: Vec<SeedEntry>,
+}
+
+// {"set", "collection", "mode": "replace"|"upsert"|"append"}
+#[derive(Serialize)]
+pub struct SeedEntry {
+    pub set: String,
+    pub collection: String,
+    pub mode: &'static str,
+}
+
+// `squash`: {"squashed": name or null when there is nothing to squash, "replaces": [name],
+// "generated": false when squashed migration already existed}
+#[derive(Serialize)]
+pub struct SquashReport {
+    pub squashed: Option<String>,
+    pub replaces: Vec<String>,
+    pub generated: bool,
+}
+
+// `snapshot`: {"path", "collections"}
+#[derive(Serialize)]
+pub struct SnapshotReport {
+    pub path: String,
+    pub collections: usize,
+}
@@ -0,0 +1,188 @@
+use anyhow::Result;
+use bson::{Bson, Document, doc};
+use futures::stream::TryStreamExt;
+use mongodb::Database;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CollectionKind {
+    Collection,
+    View,
+}
+
+pub struct CollectionSchema {
+    pub name: String,
+    pub kind: CollectionKind,
+    pub options: Document,
+    pub indexes: Vec<Document>,
+}
+
+impl CollectionSchema {
+    pub fn create_commands(&self) -> Vec<Document> {
+        let mut create = doc! { "create": &self.name };
+        create.extend(self.options.clone());
+
+        let mut commands = vec![create];
+
+        if !self.indexes.is_empty() {
+            commands.push(doc! {
+                "createIndexes": &self.name,
+                "indexes": self.indexes.clone(),
+            });
+        }
+
+        commands
+    }
+}
+
+pub struct SchemaSnapshot {
+    pub collections: Vec<CollectionSchema>,
+}
+
+impl SchemaSnapshot {
+    pub async fn capture(db: &Database, exclude: &[String]) -> Result<Self> {
+        let mut collections = vec![];
+        let mut cursor = db.run_cursor_command(doc! { "listCollections": 1 }).await?;
+
+        while let Some(spec) = cursor.try_next().await? {
+            let name = spec.get_str("name")?.to_string();
+
+            if name.starts_with("system.") || exclude.contains(&name) {
+                continue;
+            }
+
+            let kind = match spec.get_str("type").unwrap_or("collection") {
+                "view" => CollectionKind::View,
+                _ => CollectionKind::Collection,
+            };
+            let options = spec.get_document("options").cloned().unwrap_or_default();
+            let indexes = match kind {
+                CollectionKind::View => vec![],
+                CollectionKind::Collection => Self::capture_indexes(db, &name).await?,
+            };
+
+            collections.push(CollectionSchema {
+                name,
+                kind,
+                options,
+                indexes,
+            });
+        }
+
+        collections.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(Self { collections })
+    }
+
+    async fn capture_indexes(db: &Database, collection: &str) -> Result<Vec<Document>> {
+        let mut indexes = vec![];
+        let mut cursor = db
+            .run_cursor_command(doc! { "listIndexes": collection })
+            .await?;
+
+        while let Some(mut index) = cursor.try_next().await? {
+            if index.get_str("name").is_ok_and(|name| name == "_id_") {
+                continue;
+            }
+
+            // server bookkeeping fields, not accepted back by createIndexes
+            index.remove("v");
+            index.remove("ns");
+            indexes.push(index);
+        }
+
+        indexes.sort_by(|a, b| {
+            a.get_str("name")
+                .unwrap_or_default()
+                .cmp(b.get_str("name").unwrap_or_default())
+        });
+
+        Ok(indexes)
+    }
+
+    // views are created last, because they may be defined on top of collections
+    pub fn up_commands(&self) -> Vec<Document> {
+        let mut commands = vec![];
+
+        for kind in [CollectionKind::Collection, CollectionKind::View] {
+            for collection in self.collections.iter().filter(|c| c.kind == kind) {
+                commands.extend(collection.create_commands());
+            }
+        }
+
+        commands
+    }
+
+    // stable layout for code review: lists are sorted by name, indexes are sorted by name
+    pub fn to_json(&self) -> serde_json::Value {
+        use serde_json::json;
+
+        let extjson = |doc: &Document| Bson::Document(doc.clone()).into_relaxed_extjson();
+        let collections = self
+            .collections
+            .iter()
+            .filter(|c| c.kind == CollectionKind::Collection)
+            .map(|c| {
+                json!({
+                    "name": c.name,
+                    "options": extjson(&c.options),
+                    "indexes": c.indexes.iter().map(extjson).collect::<Vec<_>>(),
+                })
+            })
+            .collect::<Vec<_>>();
+        let views = self
+            .collections
+            .iter()
+            .filter(|c| c.kind == CollectionKind::View)
+            .map(|c| {
+                json!({
+                    "name": c.name,
+                    "options": extjson(&c.options),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        json!({
+            "collections": collections,
+            "views": views,
+        })
+    }
+
+    pub fn down_commands(&self) -> Vec<Document> {
+        let mut commands = vec![];
+
+        for kind in [CollectionKind::View, CollectionKind::Collection] {
+            for collection in self.collections.iter().filter(|c| c.kind == kind).rev() {
+                commands.push(doc! { "drop": &collection.name });
+            }
+        }
+
+        commands
+    }
+}
+
+// key order of objects and numeric types are ignored, server may return both changed
+pub fn is_equivalent(a: &Bson, b: &Bson) -> bool {
+    match (a, b) {
+        (Bson::Document(a), Bson::Document(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .all(|(key, value)| b.get(key).is_some_and(|other| is_equivalent(value, other)))
+        }
+        (Bson::Array(a), Bson::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| is_equivalent(a, b))
+        }
+        _ => match (as_number(a), as_number(b)) {
+            (Some(a), Some(b)) => a == b,
+            _ => a == b,
+        },
+    }
+}
+
+fn as_number(value: &Bson) -> Option<f64> {
+    match value {
+        Bson::Int32(v) => Some(*v as f64),
+        Bson::Int64(v) => Some(*v as f64),
+        Bson::Double(v) => Some(*v),
+        _ => None,
+    }
+}
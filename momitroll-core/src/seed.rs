@@ -0,0 +1,221 @@
+use anyhow::{Result, anyhow};
+use bson::{Document, doc};
+use serde::Deserialize;
+use std::{
+    fs::read_dir,
+    path::{Path, PathBuf},
+};
+use tracing::{info, warn};
+
+use crate::{
+    data::{DataReader, LoadData, contained_file},
+    loader::{find_file, load_file},
+    migration::MigrationController,
+    report::{SeedEntry, SeedReport},
+};
+
+const SEED_MANIFEST: &str = "seed";
+
+#[derive(Deserialize)]
+struct SeedManifest {
+    collections: Vec<SeedCollection>,
+}
+
+#[derive(Deserialize)]
+struct SeedCollection {
+    collection: String,
+    file: String,
+    #[serde(default)]
+    mode: SeedMode,
+    #[serde(default = "default_key")]
+    key: Vec<String>,
+}
+
+fn default_key() -> Vec<String> {
+    vec!["_id".to_string()]
+}
+
+#[derive(Deserialize, Default, Clone, Copy)]
+enum SeedMode {
+    // remove every document of collection before loading
+    #[serde(rename = "replace")]
+    Replace,
+    // replace documents matched by key fields, insert missing ones
+    #[serde(rename = "upsert")]
+    #[default]
+    Upsert,
+    #[serde(rename = "append")]
+    Append,
+}
+
+impl SeedMode {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SeedMode::Replace => "replace",
+            SeedMode::Upsert => "upsert",
+            SeedMode::Append => "append",
+        }
+    }
+}
+
+impl MigrationController {
+    pub async fn seed(&self, sets: &[String]) -> Result<SeedReport> {
+        if self.config.production {
+            return Err(anyhow!(
+                "refusing to seed production database: {}",
+                self.config.db.name
+            ));
+        }
+
+        let seed_dir = Path::new(&self.config.seed.dir);
+        let sets = if !sets.is_empty() {
+            sets.to_vec()
+        } else if !self.config.seed.sets.is_empty() {
+            self.config.seed.sets.clone()
+        } else {
+            Self::find_seed_sets(seed_dir)?
+        };
+
+        let mut collections = vec![];
+
+        if sets.is_empty() {
+            warn!("no seed sets found in {}", seed_dir.display());
+        }
+
+        for set in sets {
+            self.seed_set(&set, &seed_dir.join(&set), &mut collections)
+                .await?;
+        }
+
+        Ok(SeedReport { collections })
+    }
+
+    fn find_seed_sets(seed_dir: &Path) -> Result<Vec<String>> {
+        if !seed_dir.exists() {
+            return Err(anyhow!(
+                "seed directory doesn't exist: {}",
+                seed_dir.display()
+            ));
+        }
+
+        let mut sets = vec![];
+
+        for entry in read_dir(seed_dir)? {
+            let entry = entry?;
+
+            if entry.file_type()?.is_dir() {
+                sets.push(entry.file_name().to_string_lossy().to_string());
+            }
+        }
+
+        sets.sort();
+
+        Ok(sets)
+    }
+
+    async fn seed_set(
+        &self,
+        set: &str,
+        set_dir: &Path,
+        collections: &mut Vec<SeedEntry>,
+    ) -> Result<()> {
+        let manifest_path = find_file(set_dir.join(SEED_MANIFEST)).ok_or_else(|| {
+            anyhow!(
+                "seed manifest {}/{SEED_MANIFEST}.{{json,json5,yaml,yml,toml}} doesn't exist",
+                set_dir.display()
+            )
+        })?;
+        let manifest: SeedManifest = load_file(&manifest_path)?;
+
+        for entry in manifest.collections {
+            let file = contained_file(set_dir, &entry.file)?;
+
+            match entry.mode {
+                SeedMode::Replace => {
+                    self.db
+                        .collection::<Document>(&entry.collection)
+                        .delete_many(doc! {})
+                        .await?;
+                    self.load_data(&Self::seed_load_data(&entry, file)).await?;
+                }
+                SeedMode::Append => {
+                    self.load_data(&Self::seed_load_data(&entry, file)).await?;
+                }
+                SeedMode::Upsert => {
+                    self.upsert_data(&entry.collection, &file, &entry.key)
+                        .await?;
+                }
+            }
+
+            info!(
+                "seeded {} from set {set} ({})",
+                entry.collection,
+                entry.mode.as_str()
+            );
+            collections.push(SeedEntry {
+                set: set.to_string(),
+                collection: entry.collection,
+                mode: entry.mode.as_str(),
+            });
+        }
+
+        Ok(())
+    }
+
+    fn seed_load_data(entry: &SeedCollection, file: PathBuf) -> LoadData {
+        LoadData {
+            collection: entry.collection.clone(),
+            file,
+            batch_size: None,
+        }
+    }
+
+    async fn upsert_data(&self, collection: &str, file: &Path, key: &[String]) -> Result<()> {
+        let batch_size = self.config.migration.data_batch_size.max(1);
+        let mut updates = Vec::with_capacity(batch_size);
+
+        for doc in DataReader::open(file)? {
+            let doc = doc?;
+            let mut filter = Document::new();
+
+            for field in key {
+                let value = doc.get(field).ok_or_else(|| {
+                    anyhow!(
+                        "seed document in {} has no key field `{field}`",
+                        file.display()
+                    )
+                })?;
+                filter.insert(field, value.clone());
+            }
+
+            updates.push(doc! { "q": filter, "u": doc, "upsert": true });
+
+            if updates.len() >= batch_size {
+                self.run_upsert(collection, std::mem::take(&mut updates))
+                    .await?;
+            }
+        }
+
+        if !updates.is_empty() {
+            self.run_upsert(collection, updates).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn run_upsert(&self, collection: &str, updates: Vec<Document>) -> Result<()> {
+        let res = self
+            .db
+            .run_command(doc! { "update": collection, "updates": updates })
+            .await?;
+
+        // write errors don't fail the command itself
+        match res.get_array("writeErrors") {
+            Ok(errors) if !errors.is_empty() => Err(anyhow!(
+                "failed to upsert seed documents into {collection}: {}",
+                errors[0]
+            )),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,24 @@
+use anyhow::Result;
+use tracing::info;
+
+use crate::{migration::MigrationController, report::SnapshotReport, schema::SchemaSnapshot};
+
+impl MigrationController {
+    pub async fn snapshot(&self) -> Result<SnapshotReport> {
+        use std::io::Write;
+
+        let path = self.config.migration.snapshot_path();
+        let snapshot = SchemaSnapshot::capture(&self.db, &self.internal_collections()).await?;
+        let mut file = std::fs::File::create(&path)?;
+
+        serde_json::to_writer_pretty(&mut file, &snapshot.to_json())?;
+        writeln!(&mut file)?;
+
+        info!("schema snapshot written: {}", path.display());
+
+        Ok(SnapshotReport {
+            path: path.display().to_string(),
+            collections: snapshot.collections.len(),
+        })
+    }
+}
@@ -0,0 +1,190 @@
+use anyhow::{Result, anyhow};
+use bson::doc;
+use futures::stream::TryStreamExt;
+use std::{
+    fs::{create_dir_all, read_dir, rename},
+    path::{Path, PathBuf},
+};
+use tracing::{info, warn};
+
+use crate::{ds::*, migration::MigrationController, report::SquashReport, schema::SchemaSnapshot};
+
+const ARCHIVE_DIR: &str = ".archive";
+
+impl MigrationController {
+    pub async fn squash(&self, until: &str) -> Result<SquashReport> {
+        use bson::DateTime;
+
+        self.check_migration_collection().await?;
+
+        let collection = self
+            .db
+            .collection::<Migration>(&self.config.migration.coll_name());
+        let migrations: Vec<Migration> = collection
+            .find(doc! {})
+            .sort(doc! { "name": 1 })
+            .await?
+            .try_collect()
+            .await?;
+
+        let until_pos = migrations
+            .iter()
+            .position(|m| m.name == until)
+            .ok_or_else(|| anyhow!("migration {until} not found in changelog"))?;
+        let (range, rest) = migrations.split_at(until_pos + 1);
+
+        if range.len() < 2 {
+            warn!("nothing to squash, {until} is the first migration");
+            return Ok(SquashReport {
+                squashed: None,
+                replaces: vec![],
+                generated: false,
+            });
+        }
+
+        if let Some(migration) = range
+            .iter()
+            .find(|m| !matches!(m.status, MigrationStatus::Applied))
+        {
+            return Err(anyhow!(
+                "can't squash not applied migration: {}",
+                migration.name
+            ));
+        }
+
+        let replaces = range.iter().map(|m| m.name.clone()).collect::<Vec<_>>();
+
+        let (name, generated) = match self.find_squashed(&replaces)? {
+            Some(name) => {
+                info!("squashed migration already exists: {name}, rewriting changelog only");
+                (name, false)
+            }
+            None => {
+                if let Some(migration) = rest
+                    .iter()
+                    .find(|m| matches!(m.status, MigrationStatus::Applied))
+                {
+                    return Err(anyhow!(
+                        "migration {} after {until} is applied, snapshot would include its changes",
+                        migration.name
+                    ));
+                }
+
+                (self.generate_squashed(until, &replaces).await?, true)
+            }
+        };
+
+        let applied_at = range.iter().filter_map(|m| m.applied_at).max();
+        let description = format!("squashed {} migrations until {until}", replaces.len());
+
+        // insert before delete: an interrupted run leaves duplicates instead of losing history
+        collection
+            .insert_one(Migration {
+                name: name.clone(),
+                applied_at: Some(applied_at.unwrap_or_else(DateTime::now)),
+                status: MigrationStatus::Applied,
+                description: Some(description),
+                ..Migration::default()
+            })
+            .await?;
+        collection
+            .delete_many(doc! { "name": { "$in": &replaces } })
+            .await?;
+
+        info!("squashed {} migrations into: {name}", replaces.len());
+
+        Ok(SquashReport {
+            squashed: Some(name),
+            replaces,
+            generated,
+        })
+    }
+
+    async fn generate_squashed(&self, until: &str, replaces: &[String]) -> Result<String> {
+        use chrono::Utc;
+
+        let migration_dir = &self.config.migration.dir;
+        let timestamp = until.split('_').next().unwrap_or(until);
+        let name = format!("{timestamp}_squashed");
+        let dir_path = format!("{migration_dir}/{name}");
+
+        if Path::new(&dir_path).exists() {
+            return Err(anyhow!("migration directory already exists: {dir_path}"));
+        }
+
+        let snapshot = SchemaSnapshot::capture(&self.db, &self.internal_collections()).await?;
+        let description = format!(
+            "baseline of {} squashed migrations until {until}",
+            replaces.len()
+        );
+
+        create_dir_all(&dir_path)?;
+        Self::write_migration_file(
+            format!("{dir_path}/{name}_up.json"),
+            &MigrationContent {
+                description: description.clone(),
+                commands: snapshot.up_commands(),
+            },
+        )?;
+        Self::write_migration_file(
+            format!("{dir_path}/{name}_down.json"),
+            &MigrationContent {
+                description,
+                commands: snapshot.down_commands(),
+            },
+        )?;
+        serde_json::to_writer_pretty(
+            std::fs::File::create(format!("{dir_path}/{name}_squash.json"))?,
+            &SquashManifest {
+                replaces: replaces.to_vec(),
+                squashed_at: Utc::now().to_rfc3339(),
+            },
+        )?;
+
+        let archive_dir = PathBuf::from(migration_dir).join(ARCHIVE_DIR).join(&name);
+        create_dir_all(&archive_dir)?;
+
+        for replaced in replaces {
+            let source = PathBuf::from(migration_dir).join(replaced);
+
+            if source.exists() {
+                rename(&source, archive_dir.join(replaced))?;
+            } else {
+                warn!("migration directory not found, skip archiving: {replaced}");
+            }
+        }
+
+        info!("archived squashed migrations: {}", archive_dir.display());
+
+        Ok(name)
+    }
+
+    // squashed migrations are committed together with the archive,
+    // so other databases only have to rewrite their changelog
+    fn find_squashed(&self, replaces: &[String]) -> Result<Option<String>> {
+        let migration_dir = Path::new(&self.config.migration.dir);
+
+        if !migration_dir.exists() {
+            return Ok(None);
+        }
+
+        for entry in read_dir(migration_dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            let manifest_path = entry.path().join(format!("{name}_squash.json"));
+
+            if !manifest_path.exists() {
+                continue;
+            }
+
+            let manifest: SquashManifest =
+                serde_json::from_str(&std::fs::read_to_string(&manifest_path)?)?;
+
+            if manifest.replaces == replaces {
+                return Ok(Some(name));
+            }
+        }
+
+        Ok(None)
+    }
+}
@@ -0,0 +1,263 @@
+use anyhow::{Context, Result, anyhow};
+use bson::doc;
+use chrono::{DateTime, NaiveDate, SecondsFormat, TimeZone, Utc};
+use futures::stream::TryStreamExt;
+use glob::Pattern;
+use std::{collections::BTreeSet, fmt::Display};
+use tracing::{debug, warn};
+
+use crate::{
+    ds::{Direction, Migration, MigrationStatus},
+    loader::{checksum, load_migration, migration_dirs, migration_file},
+    migration::MigrationController,
+    report::{LastRun, MigrationEntry, MigrationState, StatusReport, StatusSummary},
+    template::Variables,
+};
+
+#[derive(Default)]
+pub struct StatusFilter {
+    pub pending: bool,
+    pub applied: bool,
+    // rfc 3339 time or date in configured timezone, e.g. `2025-01-31`
+    pub since: Option<String>,
+    // glob of migration names, e.g. `*_users_*`
+    pub name: Option<String>,
+}
+
+impl MigrationController {
+    pub async fn status(&self, filter: &StatusFilter) -> Result<StatusReport> {
+        self.check_migration_collection().await?;
+
+        let tz = self.config.timezone()?;
+        let since = filter
+            .since
+            .as_deref()
+            .map(|since| parse_since(since, &tz))
+            .transpose()?;
+        let pattern = filter
+            .name
+            .as_deref()
+            .map(Pattern::new)
+            .transpose()
+            .context("invalid glob of migration name")?;
+
+        let recorded: Vec<Migration> = self
+            .db
+            .collection::<Migration>(&self.config.migration.coll_name())
+            .find(doc! {})
+            .sort(doc! { "name": -1 })
+            .await?
+            .try_collect()
+            .await?;
+
+        let last_applied = recorded
+            .iter()
+            .filter(|m| matches!(m.status, MigrationStatus::Applied))
+            .map(|m| m.name.as_str())
+            .max();
+        let names = recorded
+            .iter()
+            .map(|m| m.name.clone())
+            .collect::<BTreeSet<_>>();
+        let last_run = last_run(&recorded, &tz);
+        let format_time = |dt: bson::DateTime| format_time(dt, &tz);
+        let keep = |name: &str,
+                    status: Option<MigrationStatus>,
+                    applied_at: Option<bson::DateTime>| {
+            (!filter.pending || matches!(status, Some(MigrationStatus::Pending)))
+                && (!filter.applied || matches!(status, Some(MigrationStatus::Applied)))
+                && since.is_none_or(|since| applied_at.is_some_and(|dt| dt.to_chrono() >= since))
+                && pattern.as_ref().is_none_or(|pattern| pattern.matches(name))
+        };
+
+        let mut summary = StatusSummary::default();
+        let mut migrations = vec![];
+
+        for migration in &recorded {
+            let state = self.reconcile(migration, last_applied);
+            summary.count(state);
+
+            if !keep(
+                &migration.name,
+                Some(migration.status),
+                migration.applied_at,
+            ) {
+                continue;
+            }
+
+            migrations.push(MigrationEntry {
+                name: migration.name.clone(),
+                state,
+                status: Some(migration.status),
+                applied_at: migration.applied_at.map(format_time),
+                description: migration.description.clone(),
+                checksum: migration.checksum,
+                duration_ms: migration.duration_ms,
+                failed_at: migration.failed_at.map(format_time),
+                error: migration.error.clone(),
+            });
+        }
+
+        for name in migration_dirs(&self.config.migration.dir)?.difference(&names) {
+            summary.count(MigrationState::Untracked);
+
+            if !keep(name, None, None) {
+                continue;
+            }
+
+            migrations.push(MigrationEntry {
+                name: name.clone(),
+                state: MigrationState::Untracked,
+                status: None,
+                applied_at: None,
+                description: None,
+                checksum: None,
+                duration_ms: None,
+                failed_at: None,
+                error: None,
+            });
+        }
+
+        if summary.total == 0 {
+            warn!("no migrations found");
+        }
+
+        migrations.sort_by(|a, b| b.name.cmp(&a.name));
+
+        Ok(StatusReport {
+            database: self.config.db.name.clone(),
+            environment: self.config.env.clone(),
+            timezone: tz.name().to_string(),
+            migrations,
+            summary,
+            last_run,
+        })
+    }
+
+    // problems are checked in order of severity, only the first one is reported
+    fn reconcile(&self, migration: &Migration, last_applied: Option<&str>) -> MigrationState {
+        let dir = &self.config.migration.dir;
+        let Ok(file_path) = migration_file(dir, &migration.name, Direction::Up) else {
+            return MigrationState::MissingFile;
+        };
+
+        if migration.failed_at.is_some() {
+            return MigrationState::Failed;
+        }
+
+        match migration.status {
+            MigrationStatus::Applied => {
+                let vars = Variables::new(&self.config, Some(&migration.name));
+                let current = load_migration(&file_path, &vars)
+                    .and_then(|content| checksum(&file_path, &content));
+
+                match (migration.checksum, current) {
+                    (Some(recorded), Ok(current)) if recorded != current => {
+                        MigrationState::Modified
+                    }
+                    (_, Err(e)) => {
+                        debug!("can't check migration {}: {e:#}", migration.name);
+                        MigrationState::Applied
+                    }
+                    _ => MigrationState::Applied,
+                }
+            }
+            MigrationStatus::Pending
+                if last_applied.is_some_and(|last| migration.name.as_str() < last) =>
+            {
+                MigrationState::OutOfOrder
+            }
+            MigrationStatus::Pending => MigrationState::Pending,
+        }
+    }
+}
+
+// migrations applied by latest `up` share its start time
+fn last_run<Tz: TimeZone>(recorded: &[Migration], tz: &Tz) -> Option<LastRun>
+where
+    Tz::Offset: Display,
+{
+    let started_at = recorded.iter().filter_map(|m| m.run_at).max()?;
+    let applied = recorded
+        .iter()
+        .filter(|m| m.run_at == Some(started_at))
+        .collect::<Vec<_>>();
+
+    Some(LastRun {
+        started_at: format_time(started_at, tz),
+        migrations: applied.len(),
+        duration_ms: applied.iter().filter_map(|m| m.duration_ms).sum(),
+    })
+}
+
+pub(crate) fn format_time<Tz: TimeZone>(dt: bson::DateTime, tz: &Tz) -> String
+where
+    Tz::Offset: Display,
+{
+    dt.to_chrono()
+        .with_timezone(tz)
+        .to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+pub(crate) fn parse_since<Tz: TimeZone>(since: &str, tz: &Tz) -> Result<DateTime<Utc>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(since) {
+        return Ok(time.with_timezone(&Utc));
+    }
+
+    NaiveDate::parse_from_str(since, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .and_then(|time| tz.from_local_datetime(&time).earliest())
+        .map(|time| time.with_timezone(&Utc))
+        .ok_or_else(|| anyhow!("`since` must be rfc 3339 time or yyyy-mm-dd date: {since}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kyiv() -> chrono::FixedOffset {
+        chrono::FixedOffset::east_opt(2 * 3600).unwrap()
+    }
+
+    fn utc(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(time)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn since_accepts_rfc_3339_time() {
+        assert_eq!(
+            parse_since("2025-01-31T10:00:00+02:00", &Utc).unwrap(),
+            utc("2025-01-31T08:00:00Z")
+        );
+    }
+
+    #[test]
+    fn since_date_is_midnight_in_timezone() {
+        assert_eq!(
+            parse_since("2025-01-31", &Utc).unwrap(),
+            utc("2025-01-31T00:00:00Z")
+        );
+        assert_eq!(
+            parse_since("2025-01-31", &kyiv()).unwrap(),
+            utc("2025-01-30T22:00:00Z")
+        );
+    }
+
+    #[test]
+    fn since_rejects_other_formats() {
+        for since in ["31.01.2025", "2025-13-01", "yesterday", ""] {
+            assert!(parse_since(since, &Utc).is_err(), "{since}");
+        }
+    }
+
+    #[test]
+    fn times_are_formatted_in_timezone() {
+        let dt = bson::DateTime::from_chrono(utc("2025-01-31T08:00:00.5Z"));
+
+        assert_eq!(format_time(dt, &Utc), "2025-01-31T08:00:00.500Z");
+        assert_eq!(format_time(dt, &kyiv()), "2025-01-31T10:00:00.500+02:00");
+    }
+}
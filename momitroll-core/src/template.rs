@@ -0,0 +1,216 @@
+use anyhow::{Result, anyhow};
+use serde_json::Value;
+use std::{collections::BTreeMap, env};
+
+use momitroll_config::{Config, ENV_PREFIX};
+
+pub struct Variables {
+    builtins: BTreeMap<&'static str, String>,
+    config: BTreeMap<String, Value>,
+}
+
+impl Variables {
+    pub fn new(config: &Config, migration: Option<&str>) -> Self {
+        use chrono::Utc;
+
+        let mut builtins = BTreeMap::new();
+        builtins.insert("MOMITROLL_DB_NAME", config.db.name.clone());
+        builtins.insert("MOMITROLL_NOW", Utc::now().to_rfc3339());
+
+        if let Some(migration) = migration {
+            builtins.insert("MOMITROLL_MIGRATION", migration.to_string());
+        }
+
+        Self {
+            builtins,
+            config: config
+                .vars
+                .iter()
+                .map(|(name, value)| (name.clone(), serde_json::json!(value)))
+                .collect(),
+        }
+    }
+
+    pub fn db_name(&self) -> &str {
+        &self.builtins["MOMITROLL_DB_NAME"]
+    }
+
+    // built-ins can't be shadowed, environment variables override config vars
+    pub fn get(&self, name: &str) -> Option<String> {
+        self.builtins
+            .get(name)
+            .cloned()
+            .or_else(|| self.env_var(name))
+            .or_else(|| self.config.get(name).map(text))
+    }
+
+    // javascript migrations can't be rendered as text, `${}` is template literal syntax there,
+    // so they get config vars with their types, environment overrides of non-string vars are
+    // parsed as json
+    pub fn to_map(&self) -> BTreeMap<String, Value> {
+        let mut vars = self.config.clone();
+        vars.extend(
+            env::vars()
+                .filter(|(name, _)| self.exposes(name))
+                .map(|(name, value)| {
+                    let value = match self.config.get(&name) {
+                        Some(Value::String(_)) | None => Value::String(value),
+                        Some(_) => serde_json::from_str(&value).unwrap_or(Value::String(value)),
+                    };
+                    (name, value)
+                }),
+        );
+        vars.extend(
+            self.builtins
+                .iter()
+                .map(|(name, value)| (name.to_string(), Value::String(value.clone()))),
+        );
+        vars
+    }
+
+    // other variables of process, e.g. database passwords, are never exposed to migrations
+    fn exposes(&self, name: &str) -> bool {
+        name.starts_with(ENV_PREFIX) || self.config.contains_key(name)
+    }
+
+    fn env_var(&self, name: &str) -> Option<String> {
+        self.exposes(name).then(|| env::var(name).ok()).flatten()
+    }
+}
+
+// substitutes `${NAME}`, `$${` is an escaped literal `${`, values are escaped like
+// json strings, the escapes are the same in double-quoted strings of json5, yaml and toml
+pub fn render(source: &str, vars: &Variables) -> Result<String> {
+    let mut res = String::with_capacity(source.len());
+    let mut undefined = vec![];
+    let mut rest = source;
+
+    while let Some(pos) = rest.find('$') {
+        res.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+
+        if rest.starts_with("$${") {
+            res.push_str("${");
+            rest = &rest[3..];
+        } else if rest.starts_with("${") {
+            let end = rest.find('}').ok_or_else(|| {
+                anyhow!(
+                    "unterminated variable: {}",
+                    rest.lines().next().unwrap_or(rest)
+                )
+            })?;
+            let name = rest[2..end].trim();
+
+            match vars.get(name) {
+                Some(value) => res.push_str(&escape(&value)),
+                None => undefined.push(name.to_string()),
+            }
+
+            rest = &rest[end + 1..];
+        } else {
+            res.push('$');
+            rest = &rest[1..];
+        }
+    }
+
+    res.push_str(rest);
+
+    if !undefined.is_empty() {
+        undefined.sort();
+        undefined.dedup();
+        return Err(anyhow!("undefined variables: {}", undefined.join(", ")));
+    }
+
+    Ok(res)
+}
+
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(value) => value.clone(),
+        value => value.to_string(),
+    }
+}
+
+fn escape(value: &str) -> String {
+    let quoted = serde_json::Value::String(value.to_string()).to_string();
+    quoted[1..quoted.len() - 1].to_string()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) fn vars(config: &[(&str, Value)]) -> Variables {
+        Variables {
+            builtins: BTreeMap::from([("MOMITROLL_DB_NAME", "app".to_string())]),
+            config: config
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.clone()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn render_substitutes_variables() {
+        let vars = vars(&[("ttl_days", 30.into())]);
+
+        assert_eq!(
+            render(
+                r#"{"db": "${MOMITROLL_DB_NAME}", "ttl": ${ ttl_days }}"#,
+                &vars
+            )
+            .unwrap(),
+            r#"{"db": "app", "ttl": 30}"#
+        );
+    }
+
+    #[test]
+    fn render_keeps_escaped_and_lone_dollars() {
+        let vars = vars(&[]);
+
+        assert_eq!(
+            render(r#"{"$set": "$${NAME}"}"#, &vars).unwrap(),
+            r#"{"$set": "${NAME}"}"#
+        );
+    }
+
+    #[test]
+    fn render_escapes_quotes_and_newlines() {
+        let vars = vars(&[("note", "say \"hi\"\nback\\slash".into())]);
+        let json = render(r#"{"note": "${note}"}"#, &vars).unwrap();
+        let yaml = render("note: \"${note}\"\n", &vars).unwrap();
+
+        assert_eq!(json, r#"{"note": "say \"hi\"\nback\\slash"}"#);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&json).unwrap()["note"],
+            "say \"hi\"\nback\\slash"
+        );
+        assert_eq!(
+            serde_yaml::from_str::<serde_json::Value>(&yaml).unwrap()["note"],
+            "say \"hi\"\nback\\slash"
+        );
+    }
+
+    #[test]
+    fn render_reports_undefined_and_unterminated_variables() {
+        let vars = vars(&[]);
+
+        assert_eq!(
+            render("${B} ${A} ${B}", &vars).unwrap_err().to_string(),
+            "undefined variables: A, B"
+        );
+        assert!(render("${A", &vars).is_err());
+    }
+
+    #[test]
+    fn environment_is_not_exposed() {
+        let vars = vars(&[("ttl_days", 30.into())]);
+        let map = vars.to_map();
+
+        assert!(vars.get("PATH").is_none());
+        assert!(!map.contains_key("PATH"));
+        assert_eq!(vars.get("ttl_days").unwrap(), "30");
+        assert_eq!(map["ttl_days"], 30);
+        assert_eq!(map["MOMITROLL_DB_NAME"], "app");
+    }
+}
@@ -0,0 +1,312 @@
+use anyhow::{Result, anyhow};
+use bson::{Bson, Document, doc};
+use std::{fs::read_dir, path::Path};
+use tracing::{debug, info, warn};
+
+use crate::{
+    ds::MigrationContent,
+    loader::{is_data_file, load_file},
+    migration::MigrationController,
+    report::{ValidatorChangeState, ValidatorEntry, ValidatorsReport},
+    schema::{CollectionKind, SchemaSnapshot, is_equivalent},
+};
+
+#[derive(Clone)]
+pub struct ValidatorSpec {
+    pub validator: Document,
+    pub level: String,
+    pub action: String,
+}
+
+impl ValidatorSpec {
+    fn from_options(options: &Document) -> Option<Self> {
+        let validator = options.get_document("validator").ok()?;
+
+        if validator.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            validator: validator.clone(),
+            level: options
+                .get_str("validationLevel")
+                .unwrap_or("strict")
+                .to_string(),
+            action: options
+                .get_str("validationAction")
+                .unwrap_or("error")
+                .to_string(),
+        })
+    }
+
+    fn apply_to(&self, command: &mut Document) {
+        command.insert("validator", self.validator.clone());
+        command.insert("validationLevel", &self.level);
+        command.insert("validationAction", &self.action);
+    }
+
+    fn changed_parts(&self, other: &ValidatorSpec) -> Vec<&'static str> {
+        let mut parts = vec![];
+
+        if !is_equivalent(
+            &Bson::Document(self.validator.clone()),
+            &Bson::Document(other.validator.clone()),
+        ) {
+            parts.push("validator");
+        }
+        if self.level != other.level {
+            parts.push("validationLevel");
+        }
+        if self.action != other.action {
+            parts.push("validationAction");
+        }
+
+        parts
+    }
+}
+
+pub enum ValidatorChangeKind {
+    // collection doesn't exist yet
+    Missing,
+    Added,
+    Changed(Vec<&'static str>),
+    // validator exists in database, but there is no file for it
+    Unmanaged,
+}
+
+pub struct ValidatorChange {
+    pub collection: String,
+    pub kind: ValidatorChangeKind,
+    pub current: Option<ValidatorSpec>,
+    pub desired: Option<ValidatorSpec>,
+}
+
+impl ValidatorChange {
+    fn entry(&self) -> ValidatorEntry {
+        let (change, parts) = match &self.kind {
+            ValidatorChangeKind::Missing => (ValidatorChangeState::MissingCollection, vec![]),
+            ValidatorChangeKind::Added => (ValidatorChangeState::Added, vec![]),
+            ValidatorChangeKind::Changed(parts) => (ValidatorChangeState::Changed, parts.clone()),
+            ValidatorChangeKind::Unmanaged => (ValidatorChangeState::Unmanaged, vec![]),
+        };
+
+        ValidatorEntry {
+            collection: self.collection.clone(),
+            change,
+            parts,
+        }
+    }
+
+    fn up_command(&self) -> Option<Document> {
+        let desired = self.desired.as_ref()?;
+
+        let mut command = match self.kind {
+            ValidatorChangeKind::Missing => doc! { "create": &self.collection },
+            ValidatorChangeKind::Added | ValidatorChangeKind::Changed(_) => {
+                doc! { "collMod": &self.collection }
+            }
+            ValidatorChangeKind::Unmanaged => return None,
+        };
+        desired.apply_to(&mut command);
+
+        Some(command)
+    }
+
+    fn down_command(&self) -> Option<Document> {
+        match (&self.kind, &self.current) {
+            (ValidatorChangeKind::Missing, _) => Some(doc! { "drop": &self.collection }),
+            (ValidatorChangeKind::Added, _) => Some(doc! {
+                "collMod": &self.collection,
+                "validator": {},
+            }),
+            (ValidatorChangeKind::Changed(_), Some(current)) => {
+                let mut command = doc! { "collMod": &self.collection };
+                current.apply_to(&mut command);
+                Some(command)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl MigrationController {
+    pub async fn validators_diff(&self) -> Result<ValidatorsReport> {
+        let report = ValidatorsReport {
+            changes: self
+                .validator_changes()
+                .await?
+                .iter()
+                .map(ValidatorChange::entry)
+                .collect(),
+        };
+
+        if !report.has_drift() {
+            info!("validators are up to date");
+        }
+
+        Ok(report)
+    }
+
+    pub async fn validators_apply(&self) -> Result<ValidatorsReport> {
+        let mut changes = vec![];
+
+        for change in self.validator_changes().await? {
+            if let Some(command) = change.up_command() {
+                self.db.run_command(command).await?;
+                info!("validator applied: {}", change.collection);
+                changes.push(change.entry());
+            }
+        }
+
+        Ok(ValidatorsReport { changes })
+    }
+
+    pub async fn validators_generate(&self, name: &str) -> Result<()> {
+        let changes = self.validator_changes().await?;
+        let up = changes
+            .iter()
+            .filter_map(|change| change.up_command())
+            .collect::<Vec<_>>();
+
+        if up.is_empty() {
+            warn!("validators are up to date, nothing to generate");
+            return Ok(());
+        }
+
+        let down = changes
+            .iter()
+            .rev()
+            .filter_map(|change| change.down_command())
+            .collect::<Vec<_>>();
+        let description = format!("update validators of {} collections", up.len());
+
+        self.create_generated(
+            name,
+            MigrationContent {
+                description: description.clone(),
+                commands: up,
+            },
+            MigrationContent {
+                description: format!("revert: {description}"),
+                commands: down,
+            },
+        )
+        .await
+    }
+
+    async fn validator_changes(&self) -> Result<Vec<ValidatorChange>> {
+        let desired = self.load_validators()?;
+        let snapshot = SchemaSnapshot::capture(&self.db, &self.internal_collections()).await?;
+        let mut changes = vec![];
+
+        for (collection, desired) in &desired {
+            let existing = snapshot.collections.iter().find(|c| &c.name == collection);
+
+            if existing.is_some_and(|c| c.kind == CollectionKind::View) {
+                return Err(anyhow!("can't set validator on view: {collection}"));
+            }
+
+            let current = existing.and_then(|c| ValidatorSpec::from_options(&c.options));
+            let kind = match (&existing, &current) {
+                (None, _) => ValidatorChangeKind::Missing,
+                (Some(_), None) => ValidatorChangeKind::Added,
+                (Some(_), Some(current)) => {
+                    let parts = current.changed_parts(desired);
+
+                    if parts.is_empty() {
+                        continue;
+                    }
+
+                    ValidatorChangeKind::Changed(parts)
+                }
+            };
+
+            changes.push(ValidatorChange {
+                collection: collection.clone(),
+                kind,
+                current,
+                desired: Some(desired.clone()),
+            });
+        }
+
+        for collection in &snapshot.collections {
+            if desired.iter().any(|(name, _)| name == &collection.name) {
+                continue;
+            }
+
+            if let Some(current) = ValidatorSpec::from_options(&collection.options) {
+                changes.push(ValidatorChange {
+                    collection: collection.name.clone(),
+                    kind: ValidatorChangeKind::Unmanaged,
+                    current: Some(current),
+                    desired: None,
+                });
+            }
+        }
+
+        Ok(changes)
+    }
+
+    // file name is collection name, content is either `$jsonSchema` itself
+    // or validator document with optional `validationLevel` and `validationAction`
+    fn load_validators(&self) -> Result<Vec<(String, ValidatorSpec)>> {
+        let config = &self.config.validators;
+        let dir = Path::new(&config.dir);
+
+        if !dir.exists() {
+            return Err(anyhow!(
+                "validators directory doesn't exist: {}",
+                dir.display()
+            ));
+        }
+
+        let mut validators = vec![];
+
+        for entry in read_dir(dir)? {
+            let path = entry?.path();
+
+            let Some(collection) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+
+            // hidden files, readmes and editor backups live next to validators
+            if !path.is_file() || collection.starts_with('.') || !is_data_file(&path) {
+                debug!("skipping {}", path.display());
+                continue;
+            }
+
+            let value: serde_json::Value = load_file(&path)?;
+            let mut content = match Bson::try_from(value)? {
+                Bson::Document(doc) => doc,
+                _ => return Err(anyhow!("validator must be an object: {}", path.display())),
+            };
+
+            let level = content
+                .remove("validationLevel")
+                .and_then(|v| v.as_str().map(str::to_string))
+                .unwrap_or_else(|| config.validation_level.clone());
+            let action = content
+                .remove("validationAction")
+                .and_then(|v| v.as_str().map(str::to_string))
+                .unwrap_or_else(|| config.validation_action.clone());
+            let validator = if content.contains_key("$jsonSchema") {
+                content
+            } else {
+                doc! { "$jsonSchema": content }
+            };
+
+            validators.push((
+                collection.to_string(),
+                ValidatorSpec {
+                    validator,
+                    level,
+                    action,
+                },
+            ));
+        }
+
+        validators.sort_by(|a, b| a.0.cmp(&b.0));
+
+        Ok(validators)
+    }
+}
@@ -0,0 +1,59 @@
+// database: app, environment: <default>, direction: down, migrations: 2
+// hooks and snapshot are not part of script, run it with `mongosh <uri> <file>`
+
+db = db.getSiblingDB("app");
+const runAt = new Date();
+
+function check(res) {
+  if (res.ok !== 1 || res.writeErrors || res.writeConcernError) {
+    throw new Error("command failed: " + EJSON.stringify(res));
+  }
+  return res;
+}
+
+function record(res, name) {
+  if (check(res).n !== 1) {
+    throw new Error("migration " + name + " is not in changelog");
+  }
+}
+
+check(db.runCommand({ insert: "_migrations_lock", documents: [{ _id: "migration", locked_at: runAt, pid: 0 }] }));
+
+try {
+  // down 20240102000000_admins: remove admins
+  check(db.runCommand(EJSON.deserialize({
+    "delete": "users",
+    "deletes": [
+      {
+        "q": {
+          "_id": {
+            "$in": [
+              {
+                "$numberInt": "1"
+              },
+              {
+                "$numberInt": "2"
+              },
+              {
+                "$numberInt": "3"
+              }
+            ]
+          }
+        },
+        "limit": {
+          "$numberInt": "0"
+        }
+      }
+    ]
+  })));
+  record(db.runCommand({ update: "_migrations", updates: [{ q: { name: "20240102000000_admins" }, u: { $set: { applied_at: null, status: "pending" }, $unset: { run_at: "", duration_ms: "", failed_at: "", error: "" } } }] }), "20240102000000_admins");
+
+  // down 20240101000000_users: drop users
+  check(db.runCommand(EJSON.deserialize({
+    "drop": "users"
+  })));
+  record(db.runCommand({ update: "_migrations", updates: [{ q: { name: "20240101000000_users" }, u: { $set: { applied_at: null, status: "pending" }, $unset: { run_at: "", duration_ms: "", failed_at: "", error: "" } } }] }), "20240101000000_users");
+
+} finally {
+  check(db.runCommand({ delete: "_migrations_lock", deletes: [{ q: { _id: "migration" }, limit: 1 }] }));
+}
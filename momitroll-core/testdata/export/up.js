@@ -0,0 +1,50 @@
+// database: app, environment: <default>, direction: up, migrations: 2
+// hooks and snapshot are not part of script, run it with `mongosh <uri> <file>`
+
+db = db.getSiblingDB("app");
+const runAt = new Date();
+
+function check(res) {
+  if (res.ok !== 1 || res.writeErrors || res.writeConcernError) {
+    throw new Error("command failed: " + EJSON.stringify(res));
+  }
+  return res;
+}
+
+function record(res, name) {
+  if (check(res).n !== 1) {
+    throw new Error("migration " + name + " is not in changelog");
+  }
+}
+
+check(db.runCommand({ insert: "_migrations_lock", documents: [{ _id: "migration", locked_at: runAt, pid: 0 }] }));
+
+try {
+  // up 20240101000000_users: create users
+  check(db.runCommand(EJSON.deserialize({
+    "create": "users"
+  })));
+  check(db.runCommand(EJSON.deserialize({
+    "createIndexes": "users",
+    "indexes": [
+      {
+        "key": {
+          "email": {
+            "$numberInt": "1"
+          }
+        },
+        "name": "email_1",
+        "unique": true
+      }
+    ]
+  })));
+  record(db.runCommand({ update: "_migrations", updates: [{ q: { name: "20240101000000_users" }, u: { $set: { applied_at: new Date(), status: "applied", description: "create users", checksum: NumberInt(1268475745), run_at: runAt }, $unset: { failed_at: "", error: "" } } }] }), "20240101000000_users");
+
+  // up 20240102000000_admins: add admins
+  check(db.runCommand(EJSON.deserialize({"insert":"users","documents":[{"_id":{"$numberInt":"1"},"email":"root@example.com"},{"_id":{"$numberInt":"2"},"email":"ops@example.com"}]})));
+  check(db.runCommand(EJSON.deserialize({"insert":"users","documents":[{"_id":{"$numberInt":"3"},"email":"dev@example.com","since":{"$date":{"$numberLong":"1704153600000"}}}]})));
+  record(db.runCommand({ update: "_migrations", updates: [{ q: { name: "20240102000000_admins" }, u: { $set: { applied_at: new Date(), status: "applied", description: "add admins", checksum: NumberInt(-1983894567), run_at: runAt }, $unset: { failed_at: "", error: "" } } }] }), "20240102000000_admins");
+
+} finally {
+  check(db.runCommand({ delete: "_migrations_lock", deletes: [{ q: { _id: "migration" }, limit: 1 }] }));
+}
@@ -1,23 +1,193 @@
+use anyhow::anyhow;
+use std::{
+    io::{IsTerminal, stderr},
+    path::PathBuf,
+    str::FromStr,
+};
 use tracing::{Level, subscriber::set_global_default};
-use tracing_subscriber::{Registry, filter::LevelFilter, fmt, layer::SubscriberExt};
-
-pub fn init_logger() -> anyhow::Result<()> {
-    set_global_default(
-        Registry::default()
-            .with(
-                fmt::layer()
-                    .with_level(true)
-                    .with_target(false)
-                    .with_thread_ids(false)
-                    .without_time()
-                    .compact(), // TODO: ??
-            )
-            .with(LevelFilter::from_level(if cfg!(debug_assertions) {
-                Level::DEBUG
-            } else {
-                Level::INFO
-            })),
-    )?;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::{
+    EnvFilter, Layer, Registry, filter::LevelFilter, fmt, layer::SubscriberExt,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    // errors only
+    Silent,
+    // warnings and errors only
+    Quiet,
+    Normal,
+    Verbose,
+    Trace,
+}
+
+impl Verbosity {
+    // `-q` and `-v` are counted, e.g. `-qq` is errors only and `-vv` is trace
+    pub fn from_counts(quiet: u8, verbose: u8) -> Self {
+        match (quiet, verbose) {
+            (0, 0) => Verbosity::Normal,
+            (0, 1) => Verbosity::Verbose,
+            (0, _) => Verbosity::Trace,
+            (1, _) => Verbosity::Quiet,
+            _ => Verbosity::Silent,
+        }
+    }
+
+    fn level(self) -> Level {
+        match self {
+            Verbosity::Silent => Level::ERROR,
+            Verbosity::Quiet => Level::WARN,
+            Verbosity::Normal if cfg!(debug_assertions) => Level::DEBUG,
+            Verbosity::Normal => Level::INFO,
+            Verbosity::Verbose => Level::DEBUG,
+            Verbosity::Trace => Level::TRACE,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    // one object per line with timestamp and fields of spans, e.g. migration name
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("unknown log format: {s}")),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+pub enum LogRotation {
+    #[default]
+    Never,
+    Hourly,
+    Daily,
+}
+
+impl FromStr for LogRotation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "never" => Ok(LogRotation::Never),
+            "hourly" => Ok(LogRotation::Hourly),
+            "daily" => Ok(LogRotation::Daily),
+            _ => Err(format!("unknown log rotation: {s}")),
+        }
+    }
+}
+
+pub struct LoggerConfig {
+    pub verbosity: Verbosity,
+    pub format: LogFormat,
+    // rotated files get date suffix, e.g. `momitroll.log.2025-01-31`
+    pub file: Option<PathBuf>,
+    pub rotation: LogRotation,
+}
+
+// logs go to stderr, so stdout holds only command output, `RUST_LOG` directives
+// take precedence over level of verbosity flags, e.g. `RUST_LOG=momitroll_core=trace`
+pub fn init_logger(config: &LoggerConfig) -> anyhow::Result<()> {
+    let filter = EnvFilter::builder()
+        .with_default_directive(LevelFilter::from_level(config.verbosity.level()).into())
+        .from_env_lossy();
+    let ansi = stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+
+    let mut layers = vec![match config.format {
+        LogFormat::Text => fmt::layer()
+            .with_writer(stderr)
+            .with_ansi(ansi)
+            .with_level(true)
+            .with_target(false)
+            .with_thread_ids(false)
+            .without_time()
+            .compact()
+            .boxed(),
+        LogFormat::Json => fmt::layer()
+            .json()
+            .with_writer(stderr)
+            .with_current_span(false)
+            .with_span_list(true)
+            .boxed(),
+    }];
+
+    if let Some(path) = &config.file {
+        let file_name = path
+            .file_name()
+            .ok_or_else(|| anyhow!("log file has no name: {}", path.display()))?;
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => std::path::Path::new("."),
+        };
+        let rotation = match config.rotation {
+            LogRotation::Never => Rotation::NEVER,
+            LogRotation::Hourly => Rotation::HOURLY,
+            LogRotation::Daily => Rotation::DAILY,
+        };
+        let appender = RollingFileAppender::builder()
+            .rotation(rotation)
+            .filename_prefix(file_name.to_string_lossy())
+            .build(dir)?;
+
+        // files always carry timestamps and never colors
+        layers.push(match config.format {
+            LogFormat::Text => fmt::layer()
+                .with_writer(appender)
+                .with_ansi(false)
+                .with_target(false)
+                .boxed(),
+            LogFormat::Json => fmt::layer()
+                .json()
+                .with_writer(appender)
+                .with_current_span(false)
+                .with_span_list(true)
+                .boxed(),
+        });
+    }
+
+    set_global_default(Registry::default().with(filter).with(layers))?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verbosity_follows_counts_of_flags() {
+        assert!(matches!(Verbosity::from_counts(0, 0), Verbosity::Normal));
+        assert!(matches!(Verbosity::from_counts(0, 1), Verbosity::Verbose));
+        assert!(matches!(Verbosity::from_counts(0, 2), Verbosity::Trace));
+        assert!(matches!(Verbosity::from_counts(0, 5), Verbosity::Trace));
+        assert!(matches!(Verbosity::from_counts(1, 0), Verbosity::Quiet));
+        assert!(matches!(Verbosity::from_counts(2, 0), Verbosity::Silent));
+        assert!(matches!(Verbosity::from_counts(3, 0), Verbosity::Silent));
+    }
+
+    #[test]
+    fn verbosity_sets_level() {
+        assert_eq!(Verbosity::Silent.level(), Level::ERROR);
+        assert_eq!(Verbosity::Quiet.level(), Level::WARN);
+        assert_eq!(Verbosity::Verbose.level(), Level::DEBUG);
+        assert_eq!(Verbosity::Trace.level(), Level::TRACE);
+        assert!(Verbosity::Quiet < Verbosity::Normal);
+    }
+
+    #[test]
+    fn log_options_parse_from_names() {
+        assert!(matches!("json".parse(), Ok(LogFormat::Json)));
+        assert!(matches!("daily".parse(), Ok(LogRotation::Daily)));
+        assert!("xml".parse::<LogFormat>().is_err());
+        assert!("weekly".parse::<LogRotation>().is_err());
+    }
+}
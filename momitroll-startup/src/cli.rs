@@ -1,8 +1,14 @@
 use clap::{
-    ColorChoice, Parser, Subcommand,
+    ArgAction, Args, ColorChoice, Parser, Subcommand, ValueEnum,
     builder::{Styles, styling::AnsiColor},
 };
 
+use std::path::PathBuf;
+
+use momitroll_config::ConfigFormat;
+use momitroll_core::migration::{ExportFormat, MigrationFormat};
+use momitroll_logger::{LogFormat, LogRotation, LoggerConfig, Verbosity};
+
 #[derive(Parser)]
 #[command(disable_version_flag = true)]
 #[command(disable_help_flag = true)]
@@ -11,31 +17,397 @@ use clap::{
 pub struct Cli {
     #[command(subcommand)]
     pub command: Command,
+    #[arg(
+        long,
+        short,
+        global = true,
+        action = ArgAction::Count,
+        conflicts_with = "verbose",
+        help = "print warnings and errors only, -qq for errors only"
+    )]
+    pub quiet: u8,
+    #[arg(
+        long,
+        short,
+        global = true,
+        action = ArgAction::Count,
+        help = "print debug logs and full error details, -vv for trace logs"
+    )]
+    pub verbose: u8,
+    #[arg(
+        long,
+        short,
+        global = true,
+        env = "MOMITROLL_OUTPUT",
+        value_name = "FORMAT",
+        default_value = "table",
+        help = "output format of command results and errors: table, plain, json, ndjson"
+    )]
+    pub output: OutputFormat,
+    #[command(flatten)]
+    pub config: ConfigArgs,
+    #[command(flatten)]
+    pub log: LogArgs,
+}
+
+#[derive(Args, Clone)]
+pub struct ConfigArgs {
+    #[arg(
+        long = "config",
+        short = 'c',
+        global = true,
+        env = "MOMITROLL_CONFIG",
+        value_name = "PATH",
+        help = "config file, searched in current and parent directories by default"
+    )]
+    pub path: Option<PathBuf>,
+    #[arg(
+        long,
+        global = true,
+        env = "MOMITROLL_ENV",
+        value_name = "NAME",
+        help = "environment profile of config file"
+    )]
+    pub env: Option<String>,
+    #[arg(
+        long = "override",
+        short = 'S',
+        global = true,
+        value_name = "KEY=VALUE",
+        help = "override config value, e.g. db.port=27018"
+    )]
+    pub overrides: Vec<String>,
+}
+
+#[derive(Args)]
+pub struct LogArgs {
+    #[arg(
+        long,
+        global = true,
+        env = "MOMITROLL_LOG_FORMAT",
+        value_name = "FORMAT",
+        default_value = "text",
+        help = "format of logs: text, json"
+    )]
+    pub log_format: LogFormat,
+    #[arg(
+        long,
+        global = true,
+        env = "MOMITROLL_LOG_FILE",
+        value_name = "PATH",
+        help = "write logs to file as well as to stderr"
+    )]
+    pub log_file: Option<PathBuf>,
+    #[arg(
+        long,
+        global = true,
+        env = "MOMITROLL_LOG_ROTATION",
+        value_name = "ROTATION",
+        default_value = "never",
+        help = "rotation of log file: never, hourly, daily"
+    )]
+    pub log_rotation: LogRotation,
+}
+
+impl Cli {
+    pub fn verbosity(&self) -> Verbosity {
+        Verbosity::from_counts(self.quiet, self.verbose)
+    }
+
+    pub fn logger_config(&self) -> LoggerConfig {
+        LoggerConfig {
+            verbosity: self.verbosity(),
+            format: self.log.log_format,
+            file: self.log.log_file.clone(),
+            rotation: self.log.log_rotation,
+        }
+    }
 }
 
 #[derive(Subcommand)]
 pub enum Command {
-    #[command(name = "init", about = "init migration folder and changelog table")]
-    Init,
+    #[command(
+        name = "init",
+        about = "init migration folder and changelog table, create config file if it's missing"
+    )]
+    Init {
+        #[command(flatten)]
+        scaffold: InitArgs,
+    },
     #[command(name = "create", about = "create new migration")]
     Create {
         #[arg(value_name = "NAME", help = "name of migration")]
         name: String,
+        #[arg(
+            long,
+            value_name = "FORMAT",
+            default_value = "json",
+            help = "format of migration files: json, json5, yaml, toml, js"
+        )]
+        format: MigrationFormat,
     },
     #[command(name = "up", about = "run all unapplied database migrations")]
-    Up,
+    Up {
+        #[arg(long, help = "print rendered commands without running them")]
+        dry_run: bool,
+    },
     #[command(name = "down", about = "undo the last applied database migrations")]
-    Down,
-    #[command(name = "status", about = "print the changelog of the database")]
-    Status,
+    Down {
+        #[arg(long, help = "print rendered commands without running them")]
+        dry_run: bool,
+    },
+    #[command(
+        name = "status",
+        about = "print the changelog of the database reconciled with migration files"
+    )]
+    Status {
+        #[arg(
+            long,
+            conflicts_with = "applied",
+            help = "show pending migrations only"
+        )]
+        pending: bool,
+        #[arg(long, help = "show applied migrations only")]
+        applied: bool,
+        #[arg(
+            long,
+            value_name = "TIME",
+            help = "show migrations applied since rfc 3339 time or yyyy-mm-dd date"
+        )]
+        since: Option<String>,
+        #[arg(
+            long,
+            value_name = "GLOB",
+            help = "show migrations with matching name, e.g. '*_users*'"
+        )]
+        name: Option<String>,
+    },
+    #[command(
+        name = "history",
+        about = "print executions of migrations, newest first"
+    )]
+    History {
+        #[arg(
+            long,
+            value_name = "GLOB",
+            help = "show migrations with matching name, e.g. '*_users*'"
+        )]
+        name: Option<String>,
+        #[arg(
+            long,
+            value_name = "DIRECTION",
+            value_parser = ["up", "down"],
+            help = "show executions of one direction"
+        )]
+        direction: Option<String>,
+        #[arg(long, help = "show failed executions only")]
+        failed: bool,
+        #[arg(
+            long,
+            value_name = "TIME",
+            help = "show executions started since rfc 3339 time or yyyy-mm-dd date"
+        )]
+        since: Option<String>,
+        #[arg(
+            long,
+            value_name = "N",
+            default_value = "50",
+            help = "maximum number of executions"
+        )]
+        limit: usize,
+    },
+    #[command(
+        name = "export",
+        about = "export pending migrations as script to be applied by hand"
+    )]
+    Export {
+        #[arg(
+            long,
+            value_name = "FORMAT",
+            default_value = "mongosh",
+            help = "format of script: mongosh"
+        )]
+        format: ExportFormat,
+        #[arg(long, value_name = "NAME", help = "first migration of exported range")]
+        from: Option<String>,
+        #[arg(long, value_name = "NAME", help = "last migration of exported range")]
+        to: Option<String>,
+        #[arg(
+            long,
+            help = "export rollback of last applied migration or of applied ones in range"
+        )]
+        down: bool,
+        #[arg(
+            long,
+            value_name = "PATH",
+            help = "write script to file instead of stdout"
+        )]
+        file: Option<PathBuf>,
+    },
     #[command(name = "drop", about = "remove last pending migration")]
     Drop,
+    #[command(
+        name = "squash",
+        about = "squash applied migrations into single baseline migration"
+    )]
+    Squash {
+        #[arg(long, value_name = "NAME", help = "last migration of squashed range")]
+        until: String,
+    },
+    #[command(name = "seed", about = "load fixture data sets into database")]
+    Seed {
+        #[arg(
+            long = "set",
+            value_name = "SET",
+            help = "seed set to load, configured sets are used by default"
+        )]
+        sets: Vec<String>,
+    },
+    #[command(
+        name = "validators",
+        about = "manage $jsonSchema validators of collections"
+    )]
+    Validators {
+        #[command(subcommand)]
+        command: ValidatorsCommand,
+    },
+    #[command(name = "indexes", about = "manage indexes declared in indexes file")]
+    Indexes {
+        #[command(subcommand)]
+        command: IndexesCommand,
+    },
+    #[command(name = "snapshot", about = "regenerate schema snapshot file")]
+    Snapshot,
+    #[command(name = "diff", about = "compare schema of two databases")]
+    Diff {
+        #[arg(
+            long,
+            value_name = "URI|ENV",
+            help = "database with desired schema, connection string or environment name"
+        )]
+        source: String,
+        #[arg(
+            long,
+            value_name = "URI|ENV",
+            help = "database to be changed, connection string or environment name"
+        )]
+        target: String,
+        #[arg(
+            long,
+            value_name = "NAME",
+            help = "generate migration which transforms target into source"
+        )]
+        generate: Option<String>,
+    },
+    #[command(
+        name = "lint",
+        about = "check migration files without connecting to database"
+    )]
+    Lint,
+    #[command(name = "config", about = "inspect effective configuration")]
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
     #[command(name = "info", about = "get info about application")]
     Info,
     #[command(name = "version", about = "get version of application")]
     Version,
 }
 
+#[derive(Subcommand)]
+pub enum ValidatorsCommand {
+    #[command(name = "diff", about = "compare validator files with database")]
+    Diff,
+    #[command(name = "apply", about = "apply validator files to database")]
+    Apply,
+    #[command(name = "generate", about = "generate migration from validator files")]
+    Generate {
+        #[arg(value_name = "NAME", help = "name of migration")]
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum IndexesCommand {
+    #[command(
+        name = "plan",
+        about = "print difference between indexes file and database"
+    )]
+    Plan,
+    #[command(name = "generate", about = "generate migration from indexes file")]
+    Generate {
+        #[arg(value_name = "NAME", help = "name of migration")]
+        name: String,
+    },
+}
+
+#[derive(Args)]
+pub struct InitArgs {
+    #[arg(long, short, help = "ask for values of created config file")]
+    pub interactive: bool,
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        default_value = "toml",
+        help = "format of created config file without --config path: toml, json"
+    )]
+    pub format: ConfigFormat,
+    #[arg(long, value_name = "DIR", help = "migration directory")]
+    pub migration_dir: Option<String>,
+    #[arg(long, value_name = "NAME", help = "name of changelog collection")]
+    pub changelog: Option<String>,
+    #[arg(
+        long,
+        value_name = "URI",
+        help = "connection string, replaces host and port"
+    )]
+    pub uri: Option<String>,
+    #[arg(long, value_name = "HOST", help = "database host")]
+    pub host: Option<String>,
+    #[arg(long, value_name = "PORT", help = "database port")]
+    pub port: Option<u16>,
+    #[arg(long, value_name = "NAME", help = "database name")]
+    pub db_name: Option<String>,
+    #[arg(long, value_name = "VAR", help = "environment variable with username")]
+    pub username_env: Option<String>,
+    #[arg(long, value_name = "VAR", help = "environment variable with password")]
+    pub password_env: Option<String>,
+    #[arg(
+        long,
+        help = "connect and create migration directory and changelog collection after config file"
+    )]
+    pub connect: bool,
+}
+
+#[derive(Subcommand)]
+pub enum ConfigCommand {
+    #[command(
+        name = "show",
+        about = "print merged configuration and source of every value"
+    )]
+    Show,
+    #[command(
+        name = "validate",
+        about = "check config file for unknown keys and invalid values"
+    )]
+    Validate,
+    #[command(name = "schema", about = "print json schema of config file")]
+    Schema,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    // aligned columns, colored on terminal
+    Table,
+    // one record per line, never colored
+    Plain,
+    // single document
+    Json,
+    // one document per record
+    Ndjson,
+}
+
 fn get_styles() -> Styles {
     Styles::styled()
         .header(AnsiColor::Green.on_default())
@@ -45,3 +417,50 @@ fn get_styles() -> Styles {
         .valid(AnsiColor::Green.on_default())
         .invalid(AnsiColor::Red.on_default())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_override_and_seed_set_are_separate_flags() {
+        for args in [
+            [
+                "--override",
+                "db.port=27018",
+                "-S",
+                "vars.ttl_days=30",
+                "seed",
+                "--set",
+                "base",
+                "--set",
+                "demo",
+            ],
+            [
+                "seed",
+                "--set",
+                "base",
+                "--override",
+                "db.port=27018",
+                "--set",
+                "demo",
+                "-S",
+                "vars.ttl_days=30",
+            ],
+        ] {
+            let cli = Cli::try_parse_from(std::iter::once("momitroll").chain(args)).unwrap();
+
+            assert_eq!(cli.config.overrides, ["db.port=27018", "vars.ttl_days=30"]);
+            assert!(matches!(cli.command, Command::Seed { sets } if sets == ["base", "demo"]));
+        }
+    }
+
+    #[test]
+    fn lint_uses_global_output_format() {
+        let cli = Cli::try_parse_from(["momitroll", "lint", "--output", "json"]).unwrap();
+
+        assert!(matches!(cli.command, Command::Lint));
+        assert!(cli.output == OutputFormat::Json);
+        assert!(Cli::try_parse_from(["momitroll", "lint", "--format", "json"]).is_err());
+    }
+}
@@ -0,0 +1,201 @@
+use anyhow::{Result, anyhow};
+use std::{
+    env,
+    fs::write,
+    io::{Write, stderr, stdin},
+    path::{Path, PathBuf},
+};
+
+use crate::cli::InitArgs;
+use momitroll_config::{CONFIG_FILE_NAME, ConfigFormat, Scaffold};
+
+// explicit path decides format by its extension, `--format` is used for default name only
+pub fn scaffold_config(path: Option<&Path>, args: &InitArgs) -> Result<PathBuf> {
+    let (path, format) = match path {
+        Some(path) => {
+            let format = path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .and_then(ConfigFormat::from_extension)
+                .ok_or_else(|| {
+                    anyhow!(
+                        "config file must have .toml or .json extension: {}",
+                        path.display()
+                    )
+                })?;
+            (path.to_path_buf(), format)
+        }
+        None => (
+            env::current_dir()?.join(format!("{CONFIG_FILE_NAME}.{}", args.format.extension())),
+            args.format,
+        ),
+    };
+
+    if path.exists() {
+        return Err(anyhow!("config file already exists: {}", path.display()));
+    }
+
+    let mut scaffold = Scaffold::default();
+    let or = |value: &Option<String>, default: &str| value.clone().unwrap_or(default.to_string());
+
+    scaffold.migration_dir = or(&args.migration_dir, &scaffold.migration_dir);
+    scaffold.changelog_coll_name = or(&args.changelog, &scaffold.changelog_coll_name);
+    scaffold.uri = args.uri.clone();
+    scaffold.host = or(&args.host, &scaffold.host);
+    scaffold.port = args.port.unwrap_or(scaffold.port);
+    scaffold.db_name = or(&args.db_name, &scaffold.db_name);
+    scaffold.username_env = args.username_env.clone();
+    scaffold.password_env = args.password_env.clone();
+
+    if args.interactive {
+        ask(&mut scaffold)?;
+    }
+
+    write(&path, scaffold.render(format)?)?;
+
+    Ok(path)
+}
+
+fn ask(scaffold: &mut Scaffold) -> Result<()> {
+    scaffold.migration_dir = prompt("migration directory", &scaffold.migration_dir)?;
+    scaffold.changelog_coll_name = prompt("changelog collection", &scaffold.changelog_coll_name)?;
+    scaffold.uri = prompt_optional("connection string", &scaffold.uri)?;
+
+    if scaffold.uri.is_none() {
+        scaffold.host = prompt("host", &scaffold.host)?;
+        scaffold.port = prompt("port", &scaffold.port.to_string())?
+            .parse()
+            .map_err(|e| anyhow!("invalid port: {e}"))?;
+    }
+
+    scaffold.db_name = prompt("database name", &scaffold.db_name)?;
+    scaffold.username_env =
+        prompt_optional("environment variable with username", &scaffold.username_env)?;
+
+    if scaffold.username_env.is_some() {
+        scaffold.password_env =
+            prompt_optional("environment variable with password", &scaffold.password_env)?;
+    }
+
+    Ok(())
+}
+
+// questions go to stderr, so stdout stays clean
+fn prompt(question: &str, default: &str) -> Result<String> {
+    eprint!("{question} [{default}]: ");
+    stderr().flush()?;
+
+    let mut answer = String::new();
+    stdin().read_line(&mut answer)?;
+
+    Ok(match answer.trim() {
+        "" => default.to_string(),
+        answer => answer.to_string(),
+    })
+}
+
+// `-` clears default value
+fn prompt_optional(question: &str, default: &Option<String>) -> Result<Option<String>> {
+    let answer = prompt(question, default.as_deref().unwrap_or("-"))?;
+
+    Ok((answer != "-").then_some(answer))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use momitroll_config::{Config, ConfigFile, Overrides, Severity, validate};
+
+    fn args(format: ConfigFormat) -> InitArgs {
+        InitArgs {
+            interactive: false,
+            format,
+            migration_dir: Some("db/migrations".to_string()),
+            changelog: None,
+            uri: None,
+            host: None,
+            port: Some(27018),
+            db_name: Some("app".to_string()),
+            username_env: Some("APP_MONGO_USER".to_string()),
+            password_env: Some("APP_MONGO_PASSWORD".to_string()),
+            connect: false,
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("momitroll-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("db/migrations")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn scaffolded_config_parses_and_validates_in_every_format() {
+        let dir = temp_dir("scaffold");
+
+        // `--format` doesn't override extension of explicit path
+        for (name, format) in [
+            ("config.toml", ConfigFormat::Json),
+            ("config.json", ConfigFormat::Toml),
+        ] {
+            let path = scaffold_config(Some(&dir.join(name)), &args(format)).unwrap();
+            let config = Config::load(Some(&path), None, &Overrides::default()).unwrap();
+            let problems = validate(&ConfigFile::from_path(path.clone()).unwrap()).unwrap();
+
+            assert_eq!(config.db.name, "app", "{name}");
+            assert_eq!(config.db.port, 27018, "{name}");
+            assert_eq!(
+                PathBuf::from(&config.migration.dir),
+                dir.canonicalize().unwrap().join("db/migrations"),
+                "{name}"
+            );
+            assert!(
+                config.credentials.is_some() && config.creds_env_vars.is_none(),
+                "{name}"
+            );
+            assert!(
+                problems.iter().all(|p| p.severity == Severity::Warning),
+                "{name}"
+            );
+            assert!(scaffold_config(Some(&path), &args(format)).is_err());
+        }
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn unknown_extension_is_rejected() {
+        let dir = temp_dir("extension");
+
+        for name in ["config.yaml", "config"] {
+            let err = scaffold_config(Some(&dir.join(name)), &args(ConfigFormat::Toml))
+                .unwrap_err()
+                .to_string();
+
+            assert!(err.starts_with("config file must have .toml or .json extension"));
+            assert!(!dir.join(name).exists());
+        }
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rendered_defaults_are_valid_config() {
+        let dir = temp_dir("defaults");
+        std::fs::create_dir(dir.join("migrations")).unwrap();
+
+        for format in [ConfigFormat::Toml, ConfigFormat::Json] {
+            let path = dir.join(format!("config.{}", format.extension()));
+            std::fs::write(&path, Scaffold::default().render(format).unwrap()).unwrap();
+
+            let config = Config::load(Some(&path), None, &Overrides::default()).unwrap();
+            let problems = validate(&ConfigFile::from_path(path).unwrap()).unwrap();
+
+            assert_eq!(config.db.connection_string(), "mongodb://localhost:27017/");
+            assert!(config.credentials.is_none());
+            assert!(problems.is_empty(), "{}", format.extension());
+        }
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,47 +1,285 @@
 mod cli;
 mod config;
+mod init;
 mod printer;
 
-use anyhow::Result;
+use anyhow::{Context, Result, anyhow};
 use clap::Parser;
+use std::{io::IsTerminal, path::PathBuf, process::ExitCode};
+use tracing::{Instrument, Span, info, info_span, warn};
 
-use cli::{Cli, Command};
-use momitroll_config::Config;
-use momitroll_core::migration::MigrationController;
-use momitroll_logger::init_logger;
-use printer::{print_info, print_version};
+use cli::{
+    Cli, Command, ConfigArgs, ConfigCommand, IndexesCommand, OutputFormat, ValidatorsCommand,
+};
+use init::scaffold_config;
+use momitroll_config::{Config, Overrides, Severity, misspelled_env_vars, schema, validate};
+use momitroll_core::{
+    diff::SchemaDiff,
+    lint::lint,
+    migration::{ExportOptions, HistoryFilter, MigrationController, StatusFilter},
+};
+use momitroll_logger::{Verbosity, init_logger};
+use momitroll_util::error::ErrorKind;
+use printer::{
+    print_config, print_diff, print_error, print_history, print_indexes, print_info, print_lint,
+    print_problems, print_report, print_run, print_status, print_validators, print_version,
+};
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let verbosity = cli.verbosity();
+
+    // colors are for terminals only, scripts get plain text
+    if cli.output != OutputFormat::Table
+        || !std::io::stdout().is_terminal()
+        || std::env::var_os("NO_COLOR").is_some()
+    {
+        colored::control::set_override(false);
+    }
+
     // init logger
-    if let Err(e) = init_logger() {
+    if let Err(e) = init_logger(&cli.logger_config()) {
         eprintln!("failed to initialize logger: {e}");
     }
 
+    // every log line carries selected environment
+    let span = match &cli.config.env {
+        Some(env) => info_span!("momitroll", env = %env),
+        None => Span::none(),
+    };
+
     // process migration
-    if let Err(e) = process_migration().await {
-        eprintln!("failed to process migration: {e}");
+    match process_migration(cli.command, &cli.config, cli.output)
+        .instrument(span)
+        .await
+    {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            print_error(&e, verbosity >= Verbosity::Verbose, cli.output);
+
+            ExitCode::from(
+                e.downcast_ref::<ErrorKind>()
+                    .map_or(1, |kind| kind.exit_code()),
+            )
+        }
+    }
+}
+
+async fn process_migration(
+    command: Command,
+    args: &ConfigArgs,
+    output: OutputFormat,
+) -> Result<()> {
+    // commands which don't need database connection
+    match command {
+        Command::Info => print_info(),
+        Command::Version => print_version(output)?,
+        Command::Config { command } => match command {
+            ConfigCommand::Show => print_config(&load_config(args)?)?,
+            ConfigCommand::Validate => {
+                let file = Config::locate(args.path.as_deref()).context(ErrorKind::Config)?;
+                let problems = validate(&file)?;
+
+                print_problems(&PathBuf::from(file), &problems);
+
+                if problems.iter().any(|p| p.severity == Severity::Error) {
+                    return Err(anyhow!("config file has problems").context(ErrorKind::Config));
+                }
+            }
+            ConfigCommand::Schema => println!("{}", serde_json::to_string_pretty(&schema())?),
+        },
+        Command::Init { ref scaffold } if Config::locate(args.path.as_deref()).is_err() => {
+            let path = scaffold_config(args.path.as_deref(), scaffold)?;
+
+            info!("config file created: {}", path.display());
+
+            if scaffold.connect {
+                process_db_command(
+                    command,
+                    &ConfigArgs {
+                        path: Some(path),
+                        ..args.clone()
+                    },
+                    output,
+                )
+                .await?;
+            } else {
+                info!(
+                    "fill in config file and run `momitroll init` again to create changelog collection"
+                );
+            }
+        }
+        Command::Lint => {
+            let report = lint(&load_config(args)?)?;
+
+            print_lint(&report, output)?;
+
+            if !report.findings.is_empty() {
+                return Err(ErrorKind::Lint.into());
+            }
+        }
+        // both databases are given, database of config is needed only to generate migration
+        Command::Diff {
+            source,
+            target,
+            generate,
+        } => {
+            let config = load_config(args)?;
+            let diff =
+                SchemaDiff::capture(&config, &Overrides::new(&args.overrides)?, &source, &target)
+                    .await?;
+
+            let report = diff.report();
+
+            print_diff(&report, output)?;
+
+            if report.changes.is_empty() {
+                return Ok(());
+            }
+
+            // generated migration resolves drift, so it's not a failure
+            match generate {
+                Some(name) => {
+                    MigrationController::new(config)
+                        .await?
+                        .diff_generate(&name, &diff)
+                        .await?
+                }
+                None => return Err(ErrorKind::Drift.into()),
+            }
+        }
+        command => process_db_command(command, args, output).await?,
     }
 
     Ok(())
 }
 
-async fn process_migration() -> Result<()> {
+fn load_config(args: &ConfigArgs) -> Result<Config> {
+    for (name, fixed) in misspelled_env_vars() {
+        warn!(
+            "environment variable {name} is ignored, levels of config keys are separated by `__`, e.g. {fixed}"
+        );
+    }
+
+    Config::load(
+        args.path.as_deref(),
+        args.env.as_deref(),
+        &Overrides::new(&args.overrides)?,
+    )
+    .context(ErrorKind::Config)
+}
+
+async fn process_db_command(
+    command: Command,
+    args: &ConfigArgs,
+    output: OutputFormat,
+) -> Result<()> {
     // load config
-    let config = Config::load()?;
+    let config = load_config(args)?;
 
     // migration controller
     let migration = MigrationController::new(config).await?;
 
-    match Cli::parse().command {
-        Command::Init => migration.init().await?,
-        Command::Create { ref name } => migration.create(name).await?,
-        Command::Up => migration.up().await?,
-        Command::Down => migration.down().await?,
-        Command::Status => migration.status().await?,
-        Command::Drop => migration.drop().await?,
-        Command::Info => print_info(),
-        Command::Version => print_version(),
+    match command {
+        Command::Init { .. } => migration.init().await?,
+        Command::Create { ref name, format } => {
+            print_report(&migration.create(name, format).await?, output)?
+        }
+        Command::Up { dry_run } => print_run(&migration.up(dry_run).await?, output)?,
+        Command::Down { dry_run } => print_run(&migration.down(dry_run).await?, output)?,
+        Command::Status {
+            pending,
+            applied,
+            ref since,
+            ref name,
+        } => {
+            let filter = StatusFilter {
+                pending,
+                applied,
+                since: since.clone(),
+                name: name.clone(),
+            };
+
+            print_status(&migration.status(&filter).await?, output)?
+        }
+        Command::History {
+            ref name,
+            ref direction,
+            failed,
+            ref since,
+            limit,
+        } => {
+            let filter = HistoryFilter {
+                name: name.clone(),
+                direction: direction.clone(),
+                failed,
+                since: since.clone(),
+                limit,
+            };
+
+            print_history(&migration.history(&filter).await?, output)?
+        }
+        Command::Export {
+            format,
+            ref from,
+            ref to,
+            down,
+            ref file,
+        } => {
+            let options = ExportOptions {
+                format,
+                from: from.clone(),
+                to: to.clone(),
+                down,
+            };
+            let script = migration.export(&options).await?;
+
+            match file {
+                Some(file) => {
+                    std::fs::write(file, script)
+                        .with_context(|| format!("failed to write script to {}", file.display()))?;
+                    info!("script written to {}", file.display());
+                }
+                None => print!("{script}"),
+            }
+        }
+        Command::Drop => print_report(&migration.drop().await?, output)?,
+        Command::Squash { ref until } => print_report(&migration.squash(until).await?, output)?,
+        Command::Seed { ref sets } => print_report(&migration.seed(sets).await?, output)?,
+        Command::Snapshot => print_report(&migration.snapshot().await?, output)?,
+        Command::Validators { ref command } => match command {
+            ValidatorsCommand::Diff => {
+                let report = migration.validators_diff().await?;
+
+                print_validators(&report, output)?;
+
+                if report.has_drift() {
+                    return Err(ErrorKind::Drift.into());
+                }
+            }
+            ValidatorsCommand::Apply => print_report(&migration.validators_apply().await?, output)?,
+            ValidatorsCommand::Generate { name } => migration.validators_generate(name).await?,
+        },
+        Command::Indexes { ref command } => match command {
+            IndexesCommand::Plan => {
+                let report = migration.indexes_plan().await?;
+
+                print_indexes(&report, output)?;
+
+                if !report.changes.is_empty() {
+                    return Err(ErrorKind::Drift.into());
+                }
+            }
+            IndexesCommand::Generate { name } => migration.indexes_generate(name).await?,
+        },
+        Command::Info
+        | Command::Version
+        | Command::Lint
+        | Command::Config { .. }
+        | Command::Diff { .. } => {
+            unreachable!("command doesn't need database connection")
+        }
     }
 
     Ok(())
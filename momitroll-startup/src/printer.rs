@@ -1,7 +1,16 @@
 use colored::Colorize;
+use serde::Serialize;
+use serde_json::json;
 
-use crate::config;
+use crate::{cli::OutputFormat, config};
+use momitroll_config::{Config, Problem, Severity};
+use momitroll_core::report::{
+    Change, DiffReport, HistoryReport, IndexesReport, LintReport, MigrationState, RunReport,
+    StatusReport, ValidatorChangeState, ValidatorsReport,
+};
 use momitroll_util::common::get_app_version;
+use momitroll_util::db::redact_uri;
+use momitroll_util::error::ErrorKind;
 
 pub const LOGO: [&str; 7] = [
     r"___ ___   ___   ___ ___  ____  ______  ____   ___   *      *     ",
@@ -24,11 +33,484 @@ pub fn print_info() {
     );
 }
 
-pub fn print_version() {
+pub fn print_version(output: OutputFormat) -> anyhow::Result<()> {
+    let report = json!({ "name": config::APP_NAME, "version": get_app_version() });
+
+    if !print_json(&report, &[&report], output)? {
+        println!(
+            "{} {}{}",
+            config::APP_NAME.blue(),
+            "v.".blue(),
+            get_app_version().red()
+        );
+    }
+
+    Ok(())
+}
+
+// json and ndjson print whole report or its records, returns false for human formats
+fn print_json<T: Serialize, R: Serialize>(
+    report: &T,
+    records: &[R],
+    output: OutputFormat,
+) -> anyhow::Result<bool> {
+    match output {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(report)?),
+        OutputFormat::Ndjson => {
+            for record in records {
+                println!("{}", serde_json::to_string(record)?);
+            }
+        }
+        OutputFormat::Table | OutputFormat::Plain => return Ok(false),
+    }
+
+    Ok(true)
+}
+
+// reports of commands which are described by logs in human formats
+pub fn print_report<T: Serialize>(report: &T, output: OutputFormat) -> anyhow::Result<()> {
+    print_json(report, std::slice::from_ref(report), output)?;
+
+    Ok(())
+}
+
+pub fn print_status(report: &StatusReport, output: OutputFormat) -> anyhow::Result<()> {
+    if print_json(report, &report.migrations, output)? {
+        return Ok(());
+    }
+
     println!(
-        "{} {}{}",
-        config::APP_NAME.blue(),
-        "v.".blue(),
-        get_app_version().red()
+        "database: {}, environment: {}, timezone: {}",
+        report.database.blue(),
+        report.environment.as_deref().unwrap_or("<default>").blue(),
+        report.timezone.blue()
+    );
+
+    let rows = report
+        .migrations
+        .iter()
+        .map(|migration| {
+            [
+                migration.name.clone(),
+                migration.state.as_str().to_string(),
+                migration
+                    .applied_at
+                    .clone()
+                    .unwrap_or_else(|| "<not applied>".to_string()),
+                migration
+                    .duration_ms
+                    .map_or_else(|| "-".to_string(), format_duration),
+                match (&migration.error, &migration.description) {
+                    (Some(error), _) => format!("error: {error}"),
+                    (None, Some(description)) => description.clone(),
+                    (None, None) => "<empty>".to_string(),
+                },
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    if output == OutputFormat::Plain {
+        for [name, state, applied_at, duration, description] in &rows {
+            println!(
+                "name: {name}, state: {state}, applied at: {applied_at}, duration: {duration}, description: {description}"
+            );
+        }
+    } else if !rows.is_empty() {
+        let header = ["NAME", "STATE", "APPLIED AT", "DURATION", "DESCRIPTION"];
+
+        print_table(header, &rows, |i, column, cell| {
+            let migration = &report.migrations[i];
+
+            match column {
+                0 => cell.blue(),
+                1 => color_state(migration.state, cell),
+                2 if migration.applied_at.is_some() => cell.green(),
+                2 => cell.red(),
+                4 if migration.error.is_some() => cell.red(),
+                4 => cell.cyan(),
+                _ => cell.normal(),
+            }
+        });
+    }
+
+    let counts = report
+        .summary
+        .states()
+        .into_iter()
+        .filter(|(_, count)| *count > 0)
+        .map(|(state, count)| color_state(state, format!("{count} {}", state.as_str())).to_string())
+        .collect::<Vec<_>>();
+
+    println!("{} migrations: {}", report.summary.total, counts.join(", "));
+
+    if let Some(last_run) = &report.last_run {
+        println!(
+            "last run: {}, {} migrations in {}",
+            last_run.started_at.green(),
+            last_run.migrations,
+            format_duration(last_run.duration_ms)
+        );
+    }
+
+    Ok(())
+}
+
+// cells are padded before coloring, escape codes would break alignment
+fn print_table<const N: usize>(
+    header: [&str; N],
+    rows: &[[String; N]],
+    style: impl Fn(usize, usize, String) -> colored::ColoredString,
+) {
+    let mut widths = header.map(str::len);
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    // last column isn't padded, it would leave trailing spaces
+    let pad = |cell: &str, column: usize| match column + 1 == N {
+        true => cell.to_string(),
+        false => format!("{cell:<width$}", width = widths[column]),
+    };
+
+    let line = header
+        .iter()
+        .enumerate()
+        .map(|(column, cell)| pad(cell, column).bold().to_string())
+        .collect::<Vec<_>>();
+    println!("{}", line.join("  "));
+
+    for (i, row) in rows.iter().enumerate() {
+        let line = row
+            .iter()
+            .enumerate()
+            .map(|(column, cell)| style(i, column, pad(cell, column)).to_string())
+            .collect::<Vec<_>>();
+        println!("{}", line.join("  "));
+    }
+}
+
+fn color_state(state: MigrationState, text: String) -> colored::ColoredString {
+    match state {
+        MigrationState::Applied => text.green(),
+        MigrationState::Pending => text.yellow(),
+        MigrationState::Untracked => text.dimmed(),
+        MigrationState::Failed
+        | MigrationState::MissingFile
+        | MigrationState::Modified
+        | MigrationState::OutOfOrder => text.red(),
+    }
+}
+
+fn format_duration(ms: i64) -> String {
+    match ms {
+        0..1000 => format!("{ms}ms"),
+        _ => format!("{:.1}s", ms as f64 / 1000.0),
+    }
+}
+
+pub fn print_history(report: &HistoryReport, output: OutputFormat) -> anyhow::Result<()> {
+    if print_json(report, &report.events, output)? {
+        return Ok(());
+    }
+
+    let rows = report
+        .events
+        .iter()
+        .map(|event| {
+            [
+                event.started_at.clone(),
+                event.direction.clone(),
+                event.migration.clone(),
+                event.outcome.clone(),
+                format_duration(event.duration_ms),
+                format!(
+                    "{}@{}",
+                    event.user.as_deref().unwrap_or("?"),
+                    event.host.as_deref().unwrap_or("?")
+                ),
+                event.version.clone(),
+                event.error.clone().unwrap_or_default(),
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    if output == OutputFormat::Plain {
+        for [
+            started_at,
+            direction,
+            migration,
+            outcome,
+            duration,
+            by,
+            version,
+            error,
+        ] in &rows
+        {
+            println!(
+                "started at: {started_at}, direction: {direction}, migration: {migration}, outcome: {outcome}, duration: {duration}, by: {by}, version: {version}, error: {error}"
+            );
+        }
+    } else if !rows.is_empty() {
+        let header = [
+            "STARTED AT",
+            "DIRECTION",
+            "MIGRATION",
+            "OUTCOME",
+            "DURATION",
+            "BY",
+            "VERSION",
+            "ERROR",
+        ];
+
+        print_table(header, &rows, |i, column, cell| {
+            let failed = report.events[i].error.is_some();
+
+            match column {
+                1 => cell.yellow(),
+                2 => cell.blue(),
+                3 | 7 if failed => cell.red(),
+                3 => cell.green(),
+                _ => cell.normal(),
+            }
+        });
+    }
+
+    println!(
+        "{} events, times in {}",
+        report.events.len(),
+        report.timezone.blue()
     );
+
+    Ok(())
+}
+
+pub fn print_run(report: &RunReport, output: OutputFormat) -> anyhow::Result<()> {
+    if print_json(report, &report.migrations, output)? || !report.dry_run {
+        return Ok(());
+    }
+
+    for migration in &report.migrations {
+        println!(
+            "{} {} ({})",
+            report.direction.yellow(),
+            migration.name.blue(),
+            migration.description.as_deref().unwrap_or_default().cyan()
+        );
+
+        for command in migration.commands.iter().flatten() {
+            println!("{}", serde_json::to_string_pretty(command)?);
+        }
+    }
+
+    Ok(())
+}
+
+pub fn print_lint(report: &LintReport, output: OutputFormat) -> anyhow::Result<()> {
+    if print_json(report, &report.findings, output)? {
+        return Ok(());
+    }
+
+    for finding in &report.findings {
+        println!(
+            "{} {}: {}",
+            format!("[{}]", finding.rule).red(),
+            finding.file.as_deref().unwrap_or(&finding.migration).blue(),
+            finding.message
+        );
+    }
+
+    match report.findings.len() {
+        0 => println!("{}", "no problems found".green()),
+        n => println!("{}", format!("{n} problems found").red()),
+    }
+
+    Ok(())
+}
+
+fn change_sign(change: Change) -> colored::ColoredString {
+    match change {
+        Change::Missing => "+".green(),
+        Change::Extra => "-".red(),
+        Change::Changed => "~".yellow(),
+    }
+}
+
+pub fn print_diff(report: &DiffReport, output: OutputFormat) -> anyhow::Result<()> {
+    if print_json(report, &report.changes, output)? {
+        return Ok(());
+    }
+
+    for change in &report.changes {
+        let action = match change.change {
+            Change::Missing => "missing in target".to_string(),
+            Change::Extra => "not present in source".to_string(),
+            Change::Changed if change.options.is_empty() => "changed".to_string(),
+            Change::Changed => format!("changed {}", change.options.join(", ")),
+        };
+
+        println!(
+            "{} {} {}: {action}",
+            change_sign(change.change),
+            change.kind,
+            change.name.blue()
+        );
+    }
+
+    Ok(())
+}
+
+pub fn print_validators(report: &ValidatorsReport, output: OutputFormat) -> anyhow::Result<()> {
+    if print_json(report, &report.changes, output)? {
+        return Ok(());
+    }
+
+    for change in &report.changes {
+        let (sign, action) = match change.change {
+            ValidatorChangeState::MissingCollection => (
+                "+".green(),
+                "collection doesn't exist, will be created with validator".to_string(),
+            ),
+            ValidatorChangeState::Added => ("+".green(), "validator will be added".to_string()),
+            ValidatorChangeState::Changed => {
+                ("~".yellow(), format!("changed {}", change.parts.join(", ")))
+            }
+            ValidatorChangeState::Unmanaged => {
+                ("?".cyan(), "validator is not managed by files".to_string())
+            }
+        };
+
+        println!("{sign} {}: {action}", change.collection.blue());
+    }
+
+    Ok(())
+}
+
+pub fn print_indexes(report: &IndexesReport, output: OutputFormat) -> anyhow::Result<()> {
+    if print_json(report, &report.changes, output)? {
+        return Ok(());
+    }
+
+    for change in &report.changes {
+        let action = match change.change {
+            Change::Missing => "missing",
+            Change::Extra => "extra",
+            Change::Changed => "changed",
+        };
+
+        println!(
+            "{} {}.{}: {action} {}",
+            change_sign(change.change),
+            change.collection.blue(),
+            change.name.cyan(),
+            change
+                .index
+                .as_ref()
+                .map(ToString::to_string)
+                .unwrap_or_default()
+        );
+    }
+
+    Ok(())
+}
+
+// in json formats error replaces report on stdout:
+// {"error": {"message", "kind", "exit_code", "causes": [message]}}
+pub fn print_error(error: &anyhow::Error, verbose: bool, output: OutputFormat) {
+    let kind = error.downcast_ref::<ErrorKind>();
+
+    // drift and lint problems are printed as report already, exit code tells them apart
+    if matches!(output, OutputFormat::Json | OutputFormat::Ndjson)
+        && matches!(kind, Some(ErrorKind::Drift | ErrorKind::Lint))
+    {
+        return;
+    }
+    let report = json!({
+        "error": {
+            "message": error.to_string(),
+            "kind": kind.map(|kind| kind.as_str()),
+            "exit_code": kind.map_or(1, |kind| kind.exit_code()),
+            "causes": error.chain().skip(1).map(ToString::to_string).collect::<Vec<_>>(),
+        }
+    });
+
+    match output {
+        OutputFormat::Json => {
+            if let Ok(report) = serde_json::to_string_pretty(&report) {
+                println!("{report}");
+            }
+            return;
+        }
+        OutputFormat::Ndjson => {
+            println!("{report}");
+            return;
+        }
+        OutputFormat::Table | OutputFormat::Plain => {}
+    }
+
+    if verbose {
+        eprintln!("{} {error:?}", "error:".red().bold());
+        return;
+    }
+
+    eprintln!("{} {error}", "error:".red().bold());
+
+    for cause in error.chain().skip(1) {
+        eprintln!("  {} {cause}", "caused by:".yellow());
+    }
+}
+
+pub fn print_config(config: &Config) -> anyhow::Result<()> {
+    println!(
+        "{} {}",
+        "config file:".green(),
+        config.path.display().to_string().blue()
+    );
+
+    if let Some(env) = &config.env {
+        println!("{} {}", "environment:".green(), env.blue());
+    }
+
+    for (key, value, source) in config.entries()? {
+        let value = match value {
+            serde_json::Value::String(uri) if key == "db.uri" => redact_uri(&uri).into(),
+            value => value,
+        };
+
+        println!(
+            "{} = {} {}",
+            key.blue(),
+            value,
+            format!("({source})").dimmed()
+        );
+    }
+
+    println!(
+        "{}",
+        "keys are overridden by MOMITROLL_* variables with `__` between levels and `_` for `-`, \
+         e.g. MOMITROLL_DB__HOST or MOMITROLL_MIGRATION__CHANGELOG_COLL_NAME, or by --override db.host=..."
+            .dimmed()
+    );
+
+    Ok(())
+}
+
+pub fn print_problems(path: &std::path::Path, problems: &[Problem]) {
+    for problem in problems {
+        let location = match (problem.line, problem.column) {
+            (Some(line), Some(column)) => format!("{}:{line}:{column}", path.display()),
+            _ => path.display().to_string(),
+        };
+        let severity = match problem.severity {
+            Severity::Error => "error".red(),
+            Severity::Warning => "warning".yellow(),
+        };
+
+        println!("{}: {severity}: {}", location.blue(), problem.message);
+    }
+
+    if problems.is_empty() {
+        println!("{}", "config is valid".green());
+    }
 }
@@ -0,0 +1,53 @@
+use std::fmt;
+
+// failure classes, attached to errors as context and mapped to process exit codes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    Config,
+    Connection,
+    Migration,
+    Drift,
+    Lock,
+    Lint,
+}
+
+impl ErrorKind {
+    // 1 is left for unclassified errors and 2 for invalid arguments reported by clap
+    pub fn exit_code(self) -> u8 {
+        match self {
+            ErrorKind::Config => 3,
+            ErrorKind::Connection => 4,
+            ErrorKind::Migration => 5,
+            ErrorKind::Drift => 6,
+            ErrorKind::Lock => 7,
+            ErrorKind::Lint => 8,
+        }
+    }
+
+    // machine readable name, part of json output
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorKind::Config => "config",
+            ErrorKind::Connection => "connection",
+            ErrorKind::Migration => "migration",
+            ErrorKind::Drift => "drift",
+            ErrorKind::Lock => "lock",
+            ErrorKind::Lint => "lint",
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ErrorKind::Config => "invalid configuration",
+            ErrorKind::Connection => "failed to connect to database",
+            ErrorKind::Migration => "migration failed",
+            ErrorKind::Drift => "drift detected",
+            ErrorKind::Lock => "migration lock is held",
+            ErrorKind::Lint => "lint problems found",
+        })
+    }
+}
+
+impl std::error::Error for ErrorKind {}
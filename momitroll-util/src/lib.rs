@@ -1,3 +1,5 @@
 pub mod common;
 pub mod db;
+pub mod error;
 pub mod file;
+pub mod process;
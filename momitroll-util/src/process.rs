@@ -0,0 +1,20 @@
+use std::process::Command;
+
+// command line is run by shell of platform, so pipes and quoting work as in terminal
+pub fn shell_command(command: &str) -> Command {
+    let mut process = match cfg!(windows) {
+        true => {
+            let mut process = Command::new("cmd");
+            process.arg("/C");
+            process
+        }
+        false => {
+            let mut process = Command::new("sh");
+            process.arg("-c");
+            process
+        }
+    };
+
+    process.arg(command);
+    process
+}